@@ -84,13 +84,13 @@ macro_rules! stack_error {
         impl $(<$generic>)? $name $(<$generic>)? {
             $(
             #[allow(non_snake_case)]
-            pub fn $stack_name<'a, T>($($stack_field : &'a $stack_field_type),*) -> Box<dyn FnOnce(T) -> Self + 'a> 
+            pub fn $stack_name<'a, T>($($stack_field : &'a (impl ToOwned<Owned = $stack_field_type> + ?Sized)),*) -> Box<dyn FnOnce(T) -> Self + 'a> 
             where
                 T: Into<Self>,
             {
                 Box::new(move |origin| {
                     let stack_info = $stack_ty_name::$stack_name {
-                        $($stack_field : $stack_field.clone() ),*
+                        $($stack_field : $stack_field.to_owned() ),*
                     };
                     match origin.into() {
                         Self::Stack{origin, mut stack} => {
@@ -0,0 +1,238 @@
+use std::{collections::VecDeque, time::Duration};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::B256,
+    rpc::types::{Block, BlockTransactionsKind},
+};
+use async_trait::async_trait;
+
+use crate::trace::{Alive, AsyncIterator};
+
+use super::{Eth, EthError};
+
+#[derive(Clone, Debug)]
+pub enum BlockEvent {
+    Block(Box<Block>),
+    // every block above this number emitted before is no longer canonical
+    Rollback(u64),
+}
+
+pub struct BlockFollower {
+    eth: Eth,
+    alive: Alive,
+    next: Option<u64>,
+    confirmations: u64,
+    poll_interval: Duration,
+    // (number, hash) of the emitted blocks, used to find the fork point on reorg
+    history: VecDeque<(u64, B256)>,
+    max_history: usize,
+}
+
+impl BlockFollower {
+    pub fn new(eth: &Eth, alive: &Alive, start: Option<u64>) -> Self {
+        Self {
+            eth: eth.clone(),
+            alive: alive.clone(),
+            next: start,
+            confirmations: 0,
+            poll_interval: Duration::from_secs(3),
+            history: VecDeque::new(),
+            max_history: 128,
+        }
+    }
+
+    pub fn with_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(1);
+        self
+    }
+
+    // goes through `Eth::get_block`, so it gets the retries, failover and
+    // cache policies of every other request
+    async fn get_block(&self, number: u64) -> Result<Option<Block>, EthError> {
+        let block = BlockNumberOrTag::Number(number).into();
+        self.eth
+            .get_block(block, BlockTransactionsKind::Hashes)
+            .await
+            .map_err(EthError::FollowBlock(&number))
+    }
+
+    async fn step(&mut self) -> Result<Option<BlockEvent>, EthError> {
+        // the reference block stands in for the head, `confirmations` counts
+        // from there
+        let (head, _) = self.eth.select_reference_block().await?;
+        let target = head.to::<u64>().saturating_sub(self.confirmations);
+        let next = *self.next.get_or_insert(target);
+        if next > target {
+            return Ok(None);
+        }
+        let Some(block) = self.get_block(next).await? else {
+            // the node is behind the head it just reported
+            return Ok(None);
+        };
+
+        if let Some((_, parent_hash)) = self.history.back() {
+            if *parent_hash != block.header.parent_hash {
+                let ancestor = self.find_ancestor().await?;
+                log::warn!(target: "eth", "reorg detected at block {}, rollback to {}", next, ancestor);
+                self.next = Some(ancestor + 1);
+                return Ok(Some(BlockEvent::Rollback(ancestor)));
+            }
+        }
+
+        let hash = block.header.hash.unwrap_or_default();
+        self.history.push_back((next, hash));
+        while self.history.len() > self.max_history {
+            self.history.pop_front();
+        }
+        self.next = Some(next + 1);
+        Ok(Some(BlockEvent::Block(Box::new(block))))
+    }
+
+    // walks the emitted blocks backwards until one still matches the chain,
+    // falling back to the oldest remembered block when the reorg is deeper
+    // than the history.
+    async fn find_ancestor(&mut self) -> Result<u64, EthError> {
+        let oldest = self.history.front().map(|n| n.0).unwrap_or_default();
+        while let Some((number, hash)) = self.history.back().cloned() {
+            let canonical = self.get_block(number).await?;
+            if canonical.and_then(|n| n.header.hash) == Some(hash) {
+                return Ok(number);
+            }
+            self.history.pop_back();
+        }
+        Ok(oldest.saturating_sub(1))
+    }
+}
+
+#[async_trait]
+impl AsyncIterator for BlockFollower {
+    type Item = BlockEvent;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.alive.is_alive() {
+                return None;
+            }
+            match self.step().await {
+                Ok(Some(event)) => return Some(event),
+                Ok(None) => {}
+                Err(err) => {
+                    log::warn!(target: "eth", "follow block {:?} failed: {:?}", self.next, err);
+                }
+            }
+            if !self.alive.sleep(self.poll_interval).await {
+                return None;
+            }
+        }
+    }
+}
+
+impl Eth {
+    pub fn follow_blocks(&self, alive: &Alive, start: Option<u64>) -> BlockFollower {
+        BlockFollower::new(self, alive, start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use alloy::{primitives::keccak256, rpc::types::Header};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        eth::mock_rpc::{mock_rpc_server, MockResponse},
+        trace::Alive,
+    };
+
+    fn fork(name: &str, from: u64, to: u64) -> Vec<B256> {
+        (from..=to)
+            .map(|n| keccak256(format!("{}-{}", name, n)))
+            .collect()
+    }
+
+    // serves the blocks of `chain`, where block n has hash chain[n]
+    fn mock_chain(chain: Arc<Mutex<Vec<B256>>>) -> String {
+        let (url, _) = mock_rpc_server(move |_, req| {
+            let chain = chain.lock().unwrap();
+            let number = match (req["method"].as_str().unwrap(), &req["params"][0]) {
+                ("eth_getBlockByNumber", Value::String(tag)) if tag == "latest" => {
+                    Some(chain.len() - 1)
+                }
+                ("eth_getBlockByNumber", Value::String(n)) => {
+                    Some(u64::from_str_radix(&n[2..], 16).unwrap() as usize)
+                }
+                ("eth_getBlockByHash", hash) => {
+                    let hash: B256 = serde_json::from_value(hash.clone()).unwrap();
+                    chain.iter().position(|n| *n == hash)
+                }
+                (method, _) => panic!("unexpected {}", method),
+            };
+            let block = number.filter(|n| *n < chain.len()).map(|n| Block::<B256> {
+                header: Header {
+                    hash: Some(chain[n]),
+                    parent_hash: chain[n.saturating_sub(1)],
+                    number: Some(n as u64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            MockResponse::result(req, block)
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_reorg_rollback_to_common_ancestor() {
+        let chain = Arc::new(Mutex::new(fork("a", 0, 5)));
+        let eth = Eth::dial(&mock_chain(chain.clone()), None).unwrap();
+        let alive = Alive::new();
+        let mut follower = eth
+            .follow_blocks(&alive, Some(1))
+            .with_poll_interval(Duration::from_millis(10));
+
+        // the head is 5, so its parent 4 is the last block emitted
+        for n in 1..=4 {
+            match follower.next().await {
+                Some(BlockEvent::Block(block)) => assert_eq!(block.header.number, Some(n)),
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+
+        // blocks from 3 on are replaced by another fork
+        {
+            let mut chain = chain.lock().unwrap();
+            chain.truncate(3);
+            chain.extend(fork("b", 3, 7));
+        }
+        assert!(matches!(
+            follower.next().await,
+            Some(BlockEvent::Rollback(2))
+        ));
+        let b = fork("b", 3, 7);
+        for n in 3..=6 {
+            match follower.next().await {
+                Some(BlockEvent::Block(block)) => {
+                    assert_eq!(block.header.number, Some(n));
+                    assert_eq!(block.header.hash, Some(b[n as usize - 3]));
+                }
+                event => panic!("unexpected {:?}", event),
+            }
+        }
+
+        alive.shutdown();
+        assert!(follower.next().await.is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use std::io;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use super::{CacheError, JsonCache};
+
+// header byte of an encoded entry, entries without one are the original
+// pretty printed `JsonCache` and always start with `{`
+const TAG_JSON: u8 = 1;
+const TAG_GZIP: u8 = 2;
+const TAG_ZSTD: u8 = 3;
+const TAG_MSGPACK: u8 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheEncoding {
+    // no tag byte, the pretty printed `JsonCache` older versions wrote. The
+    // cache still seals every entry with a checksum header on write, so
+    // older versions can't read entries written now, while unsealed legacy
+    // entries are still read.
+    #[default]
+    PrettyJson,
+    Json,
+    #[cfg(feature = "cache-gzip")]
+    Gzip,
+    // compression level, 0 picks the zstd default
+    #[cfg(feature = "cache-zstd")]
+    Zstd(i32),
+    #[cfg(feature = "cache-msgpack")]
+    MsgPack,
+}
+
+#[cfg(feature = "cache-msgpack")]
+#[derive(Serialize, Deserialize)]
+struct BinaryCache {
+    key: String,
+    value: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+}
+
+pub enum CachedValue {
+    Raw(Box<RawValue>),
+    Value(serde_json::Value),
+}
+
+impl CachedValue {
+    pub fn decode<V: DeserializeOwned>(&self) -> Result<V, serde_json::Error> {
+        match self {
+            Self::Raw(raw) => serde_json::from_str(raw.get()),
+            Self::Value(value) => Deserialize::deserialize(value),
+        }
+    }
+}
+
+pub struct CachedEntry {
+    pub expires_at: Option<u64>,
+    pub value: CachedValue,
+}
+
+impl CacheEncoding {
+    pub fn value_json<V: Serialize>(&self, data: &V) -> Result<Box<RawValue>, CacheError> {
+        let json = match self {
+            Self::PrettyJson => serde_json::to_string_pretty(data)?,
+            _ => serde_json::to_string(data)?,
+        };
+        Ok(RawValue::from_string(json)?)
+    }
+
+    pub fn encode(&self, entry: &JsonCache) -> Result<Vec<u8>, CacheError> {
+        Ok(match self {
+            Self::PrettyJson => serde_json::to_vec_pretty(entry)?,
+            Self::Json => tagged(TAG_JSON, serde_json::to_vec(entry)?),
+            #[cfg(feature = "cache-gzip")]
+            Self::Gzip => {
+                use std::io::Write;
+
+                let mut encoder = libflate::gzip::Encoder::new(vec![TAG_GZIP])?;
+                encoder.write_all(&serde_json::to_vec(entry)?)?;
+                encoder.finish().into_result()?
+            }
+            #[cfg(feature = "cache-zstd")]
+            Self::Zstd(level) => {
+                let json = serde_json::to_vec(entry)?;
+                tagged(TAG_ZSTD, zstd::encode_all(json.as_slice(), *level)?)
+            }
+            #[cfg(feature = "cache-msgpack")]
+            Self::MsgPack => {
+                let entry = BinaryCache {
+                    key: entry.key.get().to_owned(),
+                    value: serde_json::from_str(entry.value.get())?,
+                    expires_at: entry.expires_at,
+                };
+                let data = rmp_serde::to_vec_named(&entry).map_err(invalid_data)?;
+                tagged(TAG_MSGPACK, data)
+            }
+        })
+    }
+
+    // Ok(None) when the entry uses an encoding this build was compiled without
+    pub fn decode(data: &[u8]) -> Result<Option<CachedEntry>, CacheError> {
+        let Some((&tag, body)) = data.split_first() else {
+            return Err(invalid_data("empty cache entry").into());
+        };
+        let json = match tag {
+            TAG_JSON => body.to_vec(),
+            TAG_GZIP => match gunzip(body)? {
+                Some(json) => json,
+                None => return Ok(None),
+            },
+            TAG_ZSTD => match unzstd(body)? {
+                Some(json) => json,
+                None => return Ok(None),
+            },
+            TAG_MSGPACK => return decode_msgpack(body),
+            _ => data.to_vec(),
+        };
+        let entry: JsonCache = serde_json::from_slice(&json)?;
+        Ok(Some(CachedEntry {
+            expires_at: entry.expires_at,
+            value: CachedValue::Raw(entry.value),
+        }))
+    }
+}
+
+#[cfg(feature = "cache-gzip")]
+fn gunzip(body: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut out = Vec::new();
+    let mut decoder = libflate::gzip::Decoder::new(body)?;
+    io::copy(&mut decoder, &mut out)?;
+    Ok(Some(out))
+}
+
+#[cfg(not(feature = "cache-gzip"))]
+fn gunzip(_: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(feature = "cache-zstd")]
+fn unzstd(body: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    zstd::decode_all(body).map(Some)
+}
+
+#[cfg(not(feature = "cache-zstd"))]
+fn unzstd(_: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(feature = "cache-msgpack")]
+fn decode_msgpack(body: &[u8]) -> Result<Option<CachedEntry>, CacheError> {
+    let entry: BinaryCache = rmp_serde::from_slice(body).map_err(invalid_data)?;
+    Ok(Some(CachedEntry {
+        expires_at: entry.expires_at,
+        value: CachedValue::Value(entry.value),
+    }))
+}
+
+#[cfg(not(feature = "cache-msgpack"))]
+fn decode_msgpack(_: &[u8]) -> Result<Option<CachedEntry>, CacheError> {
+    Ok(None)
+}
+
+fn tagged(tag: u8, mut data: Vec<u8>) -> Vec<u8> {
+    data.insert(0, tag);
+    data
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,467 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use alloy::primitives::B256;
+
+pub trait CacheStorage: Send + Sync + std::fmt::Debug {
+    fn get(&self, key: &B256) -> io::Result<Option<Vec<u8>>>;
+    fn put(&self, key: &B256, data: &[u8]) -> io::Result<()>;
+    fn remove(&self, key: &B256) -> io::Result<()>;
+    fn keys(&self) -> io::Result<Vec<B256>>;
+
+    // takes a corrupt entry out of service, backends that can keep it aside
+    // for inspection should do so
+    fn quarantine(&self, key: &B256) -> io::Result<()> {
+        self.remove(key)
+    }
+
+    // (key, size in bytes) of every entry, used to rebuild the size index
+    fn entries(&self) -> io::Result<Vec<(B256, u64)>> {
+        let mut out = Vec::new();
+        for key in self.keys()? {
+            if let Some(data) = self.get(&key)? {
+                out.push((key, data.len() as u64));
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum CacheBackend {
+    // one `<keccak>.cache` file per entry, the original layout
+    Files(PathBuf),
+    SingleFile(PathBuf),
+    Memory(usize),
+}
+
+impl From<PathBuf> for CacheBackend {
+    fn from(path: PathBuf) -> Self {
+        Self::Files(path)
+    }
+}
+
+impl CacheBackend {
+    pub fn open(self) -> io::Result<Arc<dyn CacheStorage>> {
+        Ok(match self {
+            Self::Files(path) => {
+                std::fs::create_dir_all(&path)?;
+                Arc::new(FileStorage::new(path))
+            }
+            Self::SingleFile(path) => Arc::new(SingleFileStorage::open(path)?),
+            Self::Memory(capacity) => Arc::new(MemoryStorage::new(capacity)),
+        })
+    }
+}
+
+pub fn migrate_storage(from: &dyn CacheStorage, to: &dyn CacheStorage) -> io::Result<usize> {
+    let mut n = 0;
+    for key in from.keys()? {
+        if let Some(data) = from.get(&key)? {
+            to.put(&key, &data)?;
+            n += 1;
+        }
+    }
+    Ok(n)
+}
+
+#[derive(Clone, Debug)]
+pub struct FileStorage {
+    base_path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(base_path: PathBuf) -> Self {
+        let _ = std::fs::create_dir_all(&base_path);
+        Self { base_path }
+    }
+
+    pub fn path(&self, key: &B256) -> PathBuf {
+        self.base_path.join(format!("{}.cache", key))
+    }
+
+    pub fn quarantine_path(&self, key: &B256) -> PathBuf {
+        self.base_path
+            .join("quarantine")
+            .join(format!("{}.cache", key))
+    }
+}
+
+static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+impl CacheStorage for FileStorage {
+    fn get(&self, key: &B256) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // writes a temp file next to the entry and renames it over, so a crash
+    // never leaves a half written entry behind
+    fn put(&self, key: &B256, data: &[u8]) -> io::Result<()> {
+        let seq = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+        let tmp = self
+            .base_path
+            .join(format!("{}.{}.{}.tmp", key, std::process::id(), seq));
+        let result = File::create(&tmp)
+            .and_then(|mut file| {
+                file.write_all(data)?;
+                file.sync_all()
+            })
+            .and_then(|_| std::fs::rename(&tmp, self.path(key)));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    fn remove(&self, key: &B256) -> io::Result<()> {
+        match std::fs::remove_file(self.path(key)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn quarantine(&self, key: &B256) -> io::Result<()> {
+        let path = self.quarantine_path(key);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        match std::fs::rename(self.path(key), path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self) -> io::Result<Vec<B256>> {
+        let mut out = Vec::new();
+        for entry in std::fs::read_dir(&self.base_path)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if let Some(key) = name.strip_suffix(".cache") {
+                if let Ok(key) = B256::from_str(key) {
+                    out.push(key);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn entries(&self) -> io::Result<Vec<(B256, u64)>> {
+        let mut out = Vec::new();
+        for entry in std::fs::read_dir(&self.base_path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if let Some(key) = name.strip_suffix(".cache") {
+                if let Ok(key) = B256::from_str(key) {
+                    out.push((key, entry.metadata()?.len()));
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+const RECORD_MAGIC: u8 = 0xca;
+const RECORD_PUT: u8 = 1;
+const RECORD_REMOVE: u8 = 2;
+const RECORD_HEADER: usize = 1 + 1 + 32 + 4;
+
+// the record length is a u32, larger entries are rejected rather than truncated
+fn record_len(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cache entry of {} bytes exceeds the 4GiB record limit", len),
+        )
+    })
+}
+
+// an append-only log of `magic | op | key | len | data` records with an
+// in-memory index. A torn record at the tail is truncated on open.
+#[derive(Debug)]
+pub struct SingleFileStorage {
+    path: PathBuf,
+    inner: Mutex<SingleFileInner>,
+}
+
+#[derive(Debug)]
+struct SingleFileInner {
+    file: File,
+    index: HashMap<B256, (u64, u32)>,
+    len: u64,
+}
+
+impl SingleFileStorage {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let file_len = file.metadata()?.len();
+
+        // only the headers are read, the data is skipped over
+        let mut index = HashMap::new();
+        let mut offset = 0;
+        {
+            let mut reader = BufReader::new(&mut file);
+            reader.seek(SeekFrom::Start(0))?;
+            let mut header = [0_u8; RECORD_HEADER];
+            while offset + RECORD_HEADER as u64 <= file_len {
+                reader.read_exact(&mut header)?;
+                if header[0] != RECORD_MAGIC {
+                    break;
+                }
+                let key = B256::from_slice(&header[2..34]);
+                let len = u32::from_le_bytes(header[34..38].try_into().unwrap());
+                let data_offset = offset + RECORD_HEADER as u64;
+                if data_offset + len as u64 > file_len {
+                    break;
+                }
+                match header[1] {
+                    RECORD_PUT => {
+                        index.insert(key, (data_offset, len));
+                    }
+                    RECORD_REMOVE => {
+                        index.remove(&key);
+                    }
+                    _ => break,
+                }
+                reader.seek_relative(len as i64)?;
+                offset = data_offset + len as u64;
+            }
+        }
+        if offset < file_len {
+            log::warn!(target: "cache", "truncate {} bytes of torn records in {:?}", file_len - offset, path);
+            file.set_len(offset)?;
+        }
+
+        Ok(Self {
+            path,
+            inner: Mutex::new(SingleFileInner {
+                file,
+                index,
+                len: offset,
+            }),
+        })
+    }
+
+    fn append(inner: &mut SingleFileInner, op: u8, key: &B256, data: &[u8]) -> io::Result<u64> {
+        let len = record_len(data.len())?;
+        let mut record = Vec::with_capacity(RECORD_HEADER + data.len());
+        record.push(RECORD_MAGIC);
+        record.push(op);
+        record.extend_from_slice(key.as_slice());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(data);
+        inner.file.seek(SeekFrom::Start(inner.len))?;
+        inner.file.write_all(&record)?;
+        let data_offset = inner.len + RECORD_HEADER as u64;
+        inner.len += record.len() as u64;
+        Ok(data_offset)
+    }
+
+    // rewrites the log with only the live entries
+    pub fn compact(&self) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let tmp_path = self.path.with_extension("compact");
+        let _ = std::fs::remove_file(&tmp_path);
+        let mut tmp = Self::open(tmp_path.clone())?;
+        {
+            let tmp = tmp.inner.get_mut().unwrap();
+            let entries: Vec<_> = inner.index.iter().map(|(k, v)| (*k, *v)).collect();
+            for (key, (offset, len)) in entries {
+                let mut data = vec![0_u8; len as usize];
+                inner.file.seek(SeekFrom::Start(offset))?;
+                inner.file.read_exact(&mut data)?;
+                let data_offset = Self::append(tmp, RECORD_PUT, &key, &data)?;
+                tmp.index.insert(key, (data_offset, len));
+            }
+            tmp.file.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+        *inner = tmp.inner.into_inner().unwrap();
+        Ok(())
+    }
+}
+
+impl CacheStorage for SingleFileStorage {
+    fn get(&self, key: &B256) -> io::Result<Option<Vec<u8>>> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some((offset, len)) = inner.index.get(key).cloned() else {
+            return Ok(None);
+        };
+        let mut data = vec![0_u8; len as usize];
+        inner.file.seek(SeekFrom::Start(offset))?;
+        inner.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn put(&self, key: &B256, data: &[u8]) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let len = record_len(data.len())?;
+        let offset = Self::append(&mut inner, RECORD_PUT, key, data)?;
+        inner.index.insert(*key, (offset, len));
+        Ok(())
+    }
+
+    fn remove(&self, key: &B256) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.index.contains_key(key) {
+            Self::append(&mut inner, RECORD_REMOVE, key, &[])?;
+            inner.index.remove(key);
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> io::Result<Vec<B256>> {
+        Ok(self.inner.lock().unwrap().index.keys().cloned().collect())
+    }
+
+    fn entries(&self) -> io::Result<Vec<(B256, u64)>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.index.iter().map(|(k, v)| (*k, v.1 as u64)).collect())
+    }
+}
+
+#[derive(Debug)]
+pub struct MemoryStorage {
+    inner: Mutex<MemoryLru>,
+}
+
+#[derive(Debug)]
+struct MemoryLru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<B256, (u64, Vec<u8>)>,
+    order: BTreeMap<u64, B256>,
+}
+
+impl MemoryLru {
+    fn touch(&mut self, key: &B256) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.0);
+            entry.0 = tick;
+            self.order.insert(tick, *key);
+        }
+    }
+}
+
+impl MemoryStorage {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(MemoryLru {
+                capacity: capacity.max(1),
+                tick: 0,
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+            }),
+        }
+    }
+}
+
+impl CacheStorage for MemoryStorage {
+    fn get(&self, key: &B256) -> io::Result<Option<Vec<u8>>> {
+        let mut lru = self.inner.lock().unwrap();
+        lru.touch(key);
+        Ok(lru.entries.get(key).map(|n| n.1.clone()))
+    }
+
+    fn put(&self, key: &B256, data: &[u8]) -> io::Result<()> {
+        let mut lru = self.inner.lock().unwrap();
+        if let Some((tick, _)) = lru.entries.insert(*key, (0, data.to_vec())) {
+            lru.order.remove(&tick);
+        }
+        lru.touch(key);
+        while lru.entries.len() > lru.capacity {
+            let Some((_, oldest)) = lru.order.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, key: &B256) -> io::Result<()> {
+        let mut lru = self.inner.lock().unwrap();
+        if let Some((tick, _)) = lru.entries.remove(key) {
+            lru.order.remove(&tick);
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> io::Result<Vec<B256>> {
+        Ok(self.inner.lock().unwrap().entries.keys().cloned().collect())
+    }
+
+    fn entries(&self) -> io::Result<Vec<(B256, u64)>> {
+        let lru = self.inner.lock().unwrap();
+        Ok(lru
+            .entries
+            .iter()
+            .map(|(k, v)| (*k, v.1.len() as u64))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("base-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_single_file_reopen() {
+        let path = temp_path("single-file-reopen");
+        let (a, b) = (B256::repeat_byte(1), B256::repeat_byte(2));
+        {
+            let storage = SingleFileStorage::open(path.clone()).unwrap();
+            storage.put(&a, b"first").unwrap();
+            storage.put(&b, &vec![7; 70_000]).unwrap();
+            storage.put(&a, b"second").unwrap();
+            storage.remove(&b).unwrap();
+        }
+        // a torn record at the tail is dropped on open
+        let len = std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[RECORD_MAGIC, RECORD_PUT, 3, 3]).unwrap();
+        drop(file);
+
+        let storage = SingleFileStorage::open(path.clone()).unwrap();
+        assert_eq!(storage.get(&a).unwrap(), Some(b"second".to_vec()));
+        assert_eq!(storage.get(&b).unwrap(), None);
+        assert_eq!(storage.keys().unwrap(), vec![a]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_record_len() {
+        assert_eq!(record_len(u32::MAX as usize).unwrap(), u32::MAX);
+        let err = record_len(u32::MAX as usize + 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
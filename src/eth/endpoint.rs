@@ -0,0 +1,343 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use alloy::{providers::Provider, rpc::client::RpcClientInner, transports::BoxTransport};
+
+use crate::{thread::wait_timeout, time::Time};
+
+use super::EthError;
+
+pub type EthProvider = Arc<Box<dyn Provider<BoxTransport>>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointStrategy {
+    // always prefer the first healthy endpoint in the list
+    Fallback,
+    RoundRobin,
+    // prefer the healthy endpoint that reported the highest head
+    HighestBlock,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EndpointHealth {
+    pub consecutive_failures: usize,
+    pub latency: Option<Duration>,
+    pub last_head: Option<u64>,
+    pub last_seen: Option<Time>,
+    pub quarantined_until: Option<Time>,
+}
+
+impl EndpointHealth {
+    pub fn is_quarantined(&self, now: Time) -> bool {
+        matches!(self.quarantined_until, Some(until) if until > now)
+    }
+}
+
+pub struct Endpoint {
+    url: String,
+    provider: EthProvider,
+    health: Mutex<EndpointHealth>,
+    next_slot: Mutex<Option<Time>>,
+}
+
+impl std::fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("url", &self.url)
+            .field("health", &self.health())
+            .finish()
+    }
+}
+
+impl Endpoint {
+    pub fn new(url: String, provider: EthProvider) -> Self {
+        Self {
+            url,
+            provider,
+            health: Mutex::new(EndpointHealth::default()),
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn provider(&self) -> EthProvider {
+        self.provider.clone()
+    }
+
+    pub fn as_provider(&self) -> &dyn Provider<BoxTransport> {
+        self.provider.as_ref().as_ref()
+    }
+
+    pub fn client(&self) -> &RpcClientInner<BoxTransport> {
+        self.provider.client()
+    }
+
+    pub fn health(&self) -> EndpointHealth {
+        self.health.lock().unwrap().clone()
+    }
+}
+
+// bounds `refresh_heads` when the caller has no call timeout
+const HEAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug)]
+struct PoolOptions {
+    max_failures: usize,
+    quarantine: Duration,
+    head_refresh_interval: Duration,
+    min_interval: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    strategy: EndpointStrategy,
+    next: AtomicUsize,
+    options: Mutex<PoolOptions>,
+    head_refreshed_at: Mutex<Option<Time>>,
+}
+
+impl EndpointPool {
+    pub fn new(endpoints: Vec<Endpoint>, strategy: EndpointStrategy) -> Result<Self, EthError> {
+        if endpoints.is_empty() {
+            return Err(EthError::NoEndpoint);
+        }
+        Ok(Self {
+            endpoints,
+            strategy,
+            next: AtomicUsize::new(0),
+            options: Mutex::new(PoolOptions {
+                max_failures: 3,
+                quarantine: Duration::from_secs(30),
+                head_refresh_interval: Duration::from_secs(12),
+                min_interval: None,
+            }),
+            head_refreshed_at: Mutex::new(None),
+        })
+    }
+
+    pub fn with_quarantine(self, max_failures: usize, quarantine: Duration) -> Self {
+        self.set_quarantine(max_failures, quarantine);
+        self
+    }
+
+    pub fn with_head_refresh_interval(self, interval: Duration) -> Self {
+        self.set_head_refresh_interval(interval);
+        self
+    }
+
+    // the setters take `&self` so a pool already shared by `Eth` clones can
+    // still be tuned
+    pub fn set_quarantine(&self, max_failures: usize, quarantine: Duration) {
+        let mut options = self.options.lock().unwrap();
+        options.max_failures = max_failures.max(1);
+        options.quarantine = quarantine;
+    }
+
+    pub fn set_head_refresh_interval(&self, interval: Duration) {
+        self.options.lock().unwrap().head_refresh_interval = interval;
+    }
+
+    // caps the HTTP requests sent to each endpoint, a batch counts as one
+    pub fn with_rate_limit(self, requests_per_second: u32) -> Self {
+        self.set_rate_limit(requests_per_second);
+        self
+    }
+
+    pub fn set_rate_limit(&self, requests_per_second: u32) {
+        self.options.lock().unwrap().min_interval = match requests_per_second {
+            0 => None,
+            n => Some(Duration::from_secs(1) / n),
+        };
+    }
+
+    pub fn strategy(&self) -> EndpointStrategy {
+        self.strategy
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    // the endpoint the next request would start with, without taking a
+    // round robin turn
+    pub fn primary(&self) -> &Endpoint {
+        &self.endpoints[self.order(false)[0]]
+    }
+
+    // healthy endpoints ordered by the strategy, followed by the quarantined
+    // ones ordered by the time they are released, so a request is never left
+    // without an endpoint to try.
+    pub fn candidates(&self) -> Vec<usize> {
+        self.order(true)
+    }
+
+    fn order(&self, take_turn: bool) -> Vec<usize> {
+        let now = Time::now();
+        let health: Vec<_> = self.endpoints.iter().map(|n| n.health()).collect();
+        let (mut healthy, mut quarantined): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|idx| !health[*idx].is_quarantined(now));
+
+        match self.strategy {
+            EndpointStrategy::Fallback => {}
+            EndpointStrategy::RoundRobin => {
+                if !healthy.is_empty() {
+                    let next = match take_turn {
+                        true => self.next.fetch_add(1, Ordering::Relaxed),
+                        false => self.next.load(Ordering::Relaxed),
+                    };
+                    let start = next % healthy.len();
+                    healthy.rotate_left(start);
+                }
+            }
+            EndpointStrategy::HighestBlock => {
+                healthy.sort_by_key(|idx| std::cmp::Reverse(health[*idx].last_head));
+            }
+        }
+        quarantined.sort_by_key(|idx| health[*idx].quarantined_until);
+        healthy.extend(quarantined);
+        healthy
+    }
+
+    // waits for the next free slot of the endpoint under the rate limit
+    pub async fn throttle(&self, idx: usize) {
+        let Some(interval) = self.options.lock().unwrap().min_interval else {
+            return;
+        };
+        let wait = {
+            let mut next_slot = self.endpoints[idx].next_slot.lock().unwrap();
+            let now = Time::now();
+            let slot = match *next_slot {
+                Some(slot) if slot > now => slot,
+                _ => now,
+            };
+            *next_slot = Some(slot + interval);
+            slot.saturating_duration_since(now)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub fn report_success(&self, idx: usize, latency: Duration) {
+        let mut health = self.endpoints[idx].health.lock().unwrap();
+        health.consecutive_failures = 0;
+        health.quarantined_until = None;
+        health.latency = Some(latency);
+        health.last_seen = Some(Time::now());
+    }
+
+    pub fn report_failure(&self, idx: usize) {
+        let endpoint = &self.endpoints[idx];
+        let options = *self.options.lock().unwrap();
+        let mut health = endpoint.health.lock().unwrap();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= options.max_failures {
+            log::warn!(target: "eth", "quarantine endpoint {} for {:?} after {} failures", endpoint.url, options.quarantine, health.consecutive_failures);
+            // Duration::MAX keeps it quarantined until restart
+            health.quarantined_until = Some(Time::now().saturating_add(options.quarantine));
+        }
+    }
+
+    pub fn report_head(&self, idx: usize, head: u64, latency: Duration) {
+        self.report_success(idx, latency);
+        self.endpoints[idx].health.lock().unwrap().last_head = Some(head);
+    }
+
+    pub fn need_refresh_heads(&self) -> bool {
+        if self.strategy != EndpointStrategy::HighestBlock || self.endpoints.len() < 2 {
+            return false;
+        }
+        let interval = self.options.lock().unwrap().head_refresh_interval;
+        match *self.head_refreshed_at.lock().unwrap() {
+            Some(at) => Time::now() >= at.saturating_add(interval),
+            None => true,
+        }
+    }
+
+    // queries all endpoints at once, so the slowest one bounds the refresh
+    // rather than the sum of them
+    pub async fn refresh_heads(&self, timeout: Option<Duration>) {
+        *self.head_refreshed_at.lock().unwrap() = Some(Time::now());
+        let timeout = Some(timeout.unwrap_or(HEAD_TIMEOUT));
+        let mut handles = Vec::with_capacity(self.endpoints.len());
+        for endpoint in &self.endpoints {
+            let provider = endpoint.provider();
+            handles.push(tokio::spawn(async move {
+                let start = Time::now();
+                let head = wait_timeout(timeout, provider.get_block_number()).await;
+                (head, Time::now().saturating_duration_since(start))
+            }));
+        }
+        for (idx, handle) in handles.into_iter().enumerate() {
+            let url = &self.endpoints[idx].url;
+            match handle.await {
+                Ok((Ok(Ok(head)), latency)) => self.report_head(idx, head, latency),
+                Ok((Ok(Err(err)), _)) => {
+                    log::warn!(target: "eth", "fetch head from {} failed: {:?}", url, err);
+                    self.report_failure(idx);
+                }
+                Ok((Err(_), _)) => {
+                    log::warn!(target: "eth", "fetch head from {} timeout", url);
+                    self.report_failure(idx);
+                }
+                Err(err) => {
+                    log::warn!(target: "eth", "fetch head from {} aborted: {:?}", url, err);
+                    self.report_failure(idx);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eth::Eth;
+
+    #[test]
+    fn test_no_endpoint() {
+        let result = Eth::dial_endpoints(&[], None, EndpointStrategy::Fallback);
+        assert!(matches!(result, Err(EthError::NoEndpoint)));
+    }
+
+    #[test]
+    fn test_primary_keeps_round_robin_turn() {
+        let urls = [
+            "http://127.0.0.1:1",
+            "http://127.0.0.1:2",
+            "http://127.0.0.1:3",
+        ];
+        let eth = Eth::dial_endpoints(&urls, None, EndpointStrategy::RoundRobin).unwrap();
+        let pool = eth.endpoints();
+        assert_eq!(pool.primary().url(), urls[0]);
+        assert_eq!(pool.primary().url(), urls[0]);
+        assert_eq!(pool.candidates(), vec![0, 1, 2]);
+        assert_eq!(pool.primary().url(), urls[1]);
+        assert_eq!(pool.candidates(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn test_unbounded_durations() {
+        let urls = ["http://127.0.0.1:1", "http://127.0.0.1:2"];
+        let mut eth = Eth::dial_endpoints(&urls, None, EndpointStrategy::HighestBlock).unwrap();
+        eth.with_quarantine(1, Duration::MAX)
+            .with_head_refresh_interval(Duration::MAX);
+        let pool = eth.endpoints();
+
+        pool.report_failure(0);
+        assert!(pool.endpoints()[0].health().is_quarantined(Time::now()));
+        assert_eq!(pool.candidates(), vec![1, 0]);
+
+        *pool.head_refreshed_at.lock().unwrap() = Some(Time::now());
+        assert!(!pool.need_refresh_heads());
+    }
+}
@@ -1,35 +1,46 @@
-use std::{borrow::Cow, path::PathBuf, sync::Arc, time::Duration};
+use std::{borrow::Cow, future::Future, sync::Arc, time::Duration};
 
 use alloy::{
-    eips::BlockId,
+    eips::{BlockId, BlockNumberOrTag},
     primitives::{Address, Bytes, B256, U256},
     providers::{
         network::{Ethereum, EthereumWallet, TransactionBuilder},
         PendingTransactionBuilder, Provider, ProviderBuilder,
     },
     rpc::{
-        client::{BatchRequest, RpcClientInner},
+        client::{BatchRequest, ClientBuilder, RpcClient, RpcClientInner},
         json_rpc::{RpcParam, RpcReturn},
-        types::{BlockTransactionsKind, Transaction, TransactionRequest},
+        types::{BlockTransactionsKind, Filter, TransactionRequest},
     },
-    signers::local::{LocalSignerError, PrivateKeySigner},
+    signers::local::LocalSignerError,
     sol_types::{SolCall, SolInterface},
-    transports::{
-        http::{Client, Http},
-        RpcError, TransportErrorKind,
-    },
+    transports::{BoxTransport, RpcError, TransportErrorKind},
 };
 use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Semaphore;
 
-use crate::thread::{wait_timeout, TimeoutError};
+use crate::{
+    thread::{wait_timeout, TimeoutError},
+    time::Time,
+};
 
-use super::RequestCache;
+use super::{
+    CacheBackend, CacheDecision, CacheError, CacheMode, CacheStats, Endpoint, EndpointPool,
+    EndpointStrategy, EthProvider, EthSigner, FeeEstimate, FeeStrategy, GasLimitPolicy,
+    HttpTransport, RequestCache, RetryPolicy, StateOverride,
+};
 
 crate::stack_error! {
     #[derive(Debug)]
     name: EthError,
     stack_name: EthErrorStack,
-    error: {},
+    error: {
+        NoEndpoint,
+        MulticallFailed(Bytes),
+        InvalidMulticallHandle,
+        BlockNotFound(BlockId),
+        ReplayMiss { method: String, params: String },
+    },
     wrap: {
         Signer(LocalSignerError),
         Url(url::ParseError),
@@ -37,6 +48,9 @@ crate::stack_error! {
         Rpc(RpcError<TransportErrorKind>),
         Type(alloy::sol_types::Error),
         Timeout(TimeoutError),
+        Io(std::io::Error),
+        Join(tokio::task::JoinError),
+        Cache(CacheError),
     },
     stack: {
         OnTransact(contract: Address, sig: &'static str),
@@ -48,6 +62,21 @@ crate::stack_error! {
         BatchRequestWait(),
         WaitResponse(),
         BatchSend(),
+        RetryAttempt(attempt: usize, reason: String),
+        OnEndpoint(url: String),
+        OpenCache(backend: String),
+        OnSubscribe(name: &'static str, url: String),
+        GetLogs(from: u64, to: u64),
+        OnDecodeEvent(sig: &'static str, tx: Option<B256>, log_index: Option<u64>),
+        FollowBlock(number: u64),
+        GetBlock(block: BlockId),
+        GetTransaction(hash: B256),
+        GetReceipt(hash: B256),
+        GetLogsFilter(filter: Filter),
+        GetCode(address: Address, block: BlockId),
+        GetStorageAt(address: Address, slot: U256, block: BlockId),
+        GetProof(address: Address, block: BlockId),
+        BatchItem(index: usize, params: String),
     }
 }
 
@@ -55,17 +84,15 @@ impl EthError {
     pub fn revert(&self) -> Option<Bytes> {
         match self.origin() {
             Self::Rpc(RpcError::ErrorResp(payload)) => payload.as_revert_data(),
+            Self::MulticallFailed(data) => Some(data.clone()),
             _ => None,
         }
     }
 
     pub fn revert_data<T: SolInterface>(self) -> Result<(T, EthError), EthError> {
-        match self.origin() {
-            Self::Rpc(RpcError::ErrorResp(payload)) => match payload.as_revert_data() {
-                Some(data) => Ok((T::abi_decode(&data, true)?, self)),
-                None => Err(self),
-            },
-            _ => Err(self),
+        match self.revert() {
+            Some(data) => Ok((T::abi_decode(&data, true)?, self)),
+            None => Err(self),
         }
     }
 }
@@ -73,70 +100,332 @@ impl EthError {
 #[derive(Clone)]
 pub struct Eth {
     cache: Option<RequestCache>,
-    client: Arc<Box<dyn Provider<Http<Client>>>>,
+    cache_mode: CacheMode,
+    endpoints: Arc<EndpointPool>,
     call_timeout: Option<Duration>,
+    retry: RetryPolicy,
+    signer: Option<Address>,
+    fee_strategy: Option<FeeStrategy>,
+    gas_limit: Option<GasLimitPolicy>,
 }
 
 impl Eth {
     pub fn dial(endpoint: &str, private_key: Option<&str>) -> Result<Eth, EthError> {
-        let url = endpoint.try_into()?;
+        Self::dial_endpoints(&[endpoint], private_key, EndpointStrategy::Fallback)
+    }
+
+    pub fn dial_endpoints(
+        endpoints: &[&str],
+        private_key: Option<&str>,
+        strategy: EndpointStrategy,
+    ) -> Result<Eth, EthError> {
+        let signer = match private_key {
+            Some(pk) => Some(EthSigner::private_key(pk)?),
+            None => None,
+        };
+        Self::dial_with_signer(endpoints, signer, strategy)
+    }
 
-        let provider: Box<dyn Provider<Http<Client>>> = match private_key {
-            Some(pk) => {
-                let signer = pk.parse::<PrivateKeySigner>()?;
-                let wallet = EthereumWallet::new(signer);
+    pub fn dial_with_signer(
+        endpoints: &[&str],
+        signer: Option<EthSigner>,
+        strategy: EndpointStrategy,
+    ) -> Result<Eth, EthError> {
+        let wallet = signer.as_ref().map(|n| n.wallet());
+        let mut list = Vec::with_capacity(endpoints.len());
+        for endpoint in endpoints {
+            let provider = Self::build_provider(endpoint, wallet.clone())?;
+            list.push(Endpoint::new(endpoint.to_string(), provider));
+        }
+        let mut eth = Self::from_pool(EndpointPool::new(list, strategy)?);
+        eth.signer = signer.map(|n| n.address());
+        Ok(eth)
+    }
+
+    pub fn from_pool(endpoints: EndpointPool) -> Eth {
+        Eth {
+            endpoints: Arc::new(endpoints),
+            call_timeout: None,
+            cache: None,
+            cache_mode: CacheMode::default(),
+            retry: RetryPolicy::none(),
+            signer: None,
+            fee_strategy: None,
+            gas_limit: None,
+        }
+    }
+
+    // accepts http, ws and ipc endpoints, picked by the scheme of each endpoint
+    pub async fn connect(endpoint: &str, private_key: Option<&str>) -> Result<Eth, EthError> {
+        Self::connect_endpoints(&[endpoint], private_key, EndpointStrategy::Fallback).await
+    }
+
+    pub async fn connect_endpoints(
+        endpoints: &[&str],
+        private_key: Option<&str>,
+        strategy: EndpointStrategy,
+    ) -> Result<Eth, EthError> {
+        let signer = match private_key {
+            Some(pk) => Some(EthSigner::private_key(pk)?),
+            None => None,
+        };
+        Self::connect_with_signer(endpoints, signer, strategy).await
+    }
+
+    pub async fn connect_with_signer(
+        endpoints: &[&str],
+        signer: Option<EthSigner>,
+        strategy: EndpointStrategy,
+    ) -> Result<Eth, EthError> {
+        let wallet = signer.as_ref().map(|n| n.wallet());
+        let mut list = Vec::with_capacity(endpoints.len());
+        for endpoint in endpoints {
+            let provider = Self::connect_provider(endpoint, wallet.clone()).await?;
+            list.push(Endpoint::new(endpoint.to_string(), provider));
+        }
+        let mut eth = Self::from_pool(EndpointPool::new(list, strategy)?);
+        eth.signer = signer.map(|n| n.address());
+        Ok(eth)
+    }
+
+    fn build_provider(
+        endpoint: &str,
+        wallet: Option<EthereumWallet>,
+    ) -> Result<EthProvider, EthError> {
+        let transport = HttpTransport::new(endpoint.try_into()?);
+        let is_local = transport.is_local();
+        let client = ClientBuilder::default()
+            .transport(transport, is_local)
+            .boxed();
+        Ok(Self::provider_from_client(client, wallet))
+    }
+
+    async fn connect_provider(
+        endpoint: &str,
+        wallet: Option<EthereumWallet>,
+    ) -> Result<EthProvider, EthError> {
+        // keep http on our transport so `Retry-After` still reaches the retry policy
+        if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            return Self::build_provider(endpoint, wallet);
+        }
+        let provider: Box<dyn Provider<BoxTransport>> = match wallet {
+            Some(wallet) => {
                 let provider = ProviderBuilder::new()
                     .with_recommended_fillers()
                     .wallet(wallet)
-                    .on_http(url);
+                    .on_builtin(endpoint)
+                    .await?;
                 Box::new(provider)
             }
             None => {
-                let provider = ProviderBuilder::new().on_http(url);
+                let provider = ProviderBuilder::new().on_builtin(endpoint).await?;
                 Box::new(provider)
             }
         };
+        Ok(Arc::new(provider))
+    }
 
-        Ok(Eth {
-            client: Arc::new(provider),
-            call_timeout: None,
-            cache: None,
-        })
+    fn provider_from_client(
+        client: RpcClient<BoxTransport>,
+        wallet: Option<EthereumWallet>,
+    ) -> EthProvider {
+        let provider: Box<dyn Provider<BoxTransport>> = match wallet {
+            Some(wallet) => {
+                let provider = ProviderBuilder::new()
+                    .with_recommended_fillers()
+                    .wallet(wallet)
+                    .on_client(client);
+                Box::new(provider)
+            }
+            None => {
+                let provider = ProviderBuilder::new().on_client(client);
+                Box::new(provider)
+            }
+        };
+        Arc::new(provider)
+    }
+
+    pub fn with_cache(&mut self, backend: impl Into<CacheBackend>) -> Result<&mut Self, EthError> {
+        let backend = backend.into();
+        let cache = RequestCache::open(backend.clone())
+            .map_err(EthError::OpenCache(&format!("{:?}", backend)))?;
+        self.cache = Some(cache);
+        Ok(self)
+    }
+
+    pub fn with_request_cache(&mut self, cache: RequestCache) -> &mut Self {
+        self.cache = Some(cache);
+        self
     }
 
-    pub fn with_cache(&mut self, base_path: PathBuf) -> &mut Self {
-        self.cache = Some(RequestCache::new(base_path));
+    pub fn with_cache_mode(&mut self, mode: CacheMode) -> &mut Self {
+        self.cache_mode = mode;
         self
     }
 
+    pub fn cache_mode(&self) -> CacheMode {
+        self.cache_mode
+    }
+
+    pub fn cache(&self) -> Option<&RequestCache> {
+        self.cache.as_ref()
+    }
+
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    // the deadline of a whole call, every retry attempt and backoff included
     pub fn with_call_timeout(&mut self, call_timeout: Option<Duration>) -> &mut Self {
         self.call_timeout = call_timeout;
         self
     }
 
+    pub fn with_retry(&mut self, retry: RetryPolicy) -> &mut Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_quarantine(&mut self, max_failures: usize, quarantine: Duration) -> &mut Self {
+        self.endpoints.set_quarantine(max_failures, quarantine);
+        self
+    }
+
+    pub fn with_head_refresh_interval(&mut self, interval: Duration) -> &mut Self {
+        self.endpoints.set_head_refresh_interval(interval);
+        self
+    }
+
+    // caps the HTTP requests sent to each endpoint, 0 disables the limit
+    pub fn with_rate_limit(&mut self, requests_per_second: u32) -> &mut Self {
+        self.endpoints.set_rate_limit(requests_per_second);
+        self
+    }
+
+    pub fn with_fee_strategy(&mut self, strategy: FeeStrategy) -> &mut Self {
+        self.fee_strategy = Some(strategy);
+        self
+    }
+
+    pub fn with_gas_limit_policy(&mut self, policy: GasLimitPolicy) -> &mut Self {
+        self.gas_limit = Some(policy);
+        self
+    }
+
+    pub fn signer_address(&self) -> Option<Address> {
+        self.signer
+    }
+
+    pub async fn estimate_fees(
+        &self,
+        request: &TransactionRequest,
+    ) -> Result<FeeEstimate, EthError> {
+        let provider = self.provider();
+        let mut request = request.clone();
+        if request.from.is_none() {
+            request.from = self.signer;
+        }
+        let gas = provider.estimate_gas(&request).await?;
+        let mut estimate = FeeEstimate {
+            gas_limit: self.gas_limit.clone().unwrap_or_default().apply(gas),
+            ..Default::default()
+        };
+        self.fee_strategy
+            .clone()
+            .unwrap_or_default()
+            .estimate(&**provider, &mut estimate)
+            .await?;
+        Ok(estimate)
+    }
+
+    pub async fn dry_run<T: SolCall>(
+        &self,
+        contract: Address,
+        call: &T,
+    ) -> Result<FeeEstimate, EthError> {
+        let tx = TransactionRequest::default().with_call(call).to(contract);
+        let estimate = self
+            .estimate_fees(&tx)
+            .await
+            .map_err(EthError::OnTransact(&contract, &T::SIGNATURE))?;
+        log::info!(target: "eth", "dry run {} on {:?}: {:?}, max cost: {}", T::SIGNATURE, contract, estimate, estimate.max_cost());
+        Ok(estimate)
+    }
+
+    // fills gas and fees by the configured policies, leaving them to the
+    // provider fillers when nothing is configured.
+    pub async fn prepare_transaction(
+        &self,
+        mut request: TransactionRequest,
+    ) -> Result<TransactionRequest, EthError> {
+        if self.fee_strategy.is_none() && self.gas_limit.is_none() {
+            return Ok(request);
+        }
+        let estimate = self.estimate_fees(&request).await?;
+        estimate.apply(&mut request);
+        Ok(request)
+    }
+
     pub async fn transact<T: SolCall>(
         &self,
         contract: Address,
         call: &T,
-    ) -> Result<PendingTransactionBuilder<Http<Client>, Ethereum>, EthError> {
+    ) -> Result<PendingTransactionBuilder<'_, BoxTransport, Ethereum>, EthError> {
         let tx = TransactionRequest::default().with_call(call).to(contract);
-        let result = self
-            .client
-            .send_transaction(tx)
+        // keyed by the request before nonce and fees are filled in, replay
+        // only hands back the recorded hash, waiting on it still needs a node
+        let key = match self.signer {
+            Some(from) => tx.clone().from(from),
+            None => tx.clone(),
+        };
+        // borrowed from `self`, so the returned builder can outlive this call
+        let provider = self.endpoints.primary().as_provider();
+        let remote = async {
+            let tx = self.prepare_transaction(tx).await?;
+            let pending = provider.send_transaction(tx).await?;
+            Ok::<_, EthError>(*pending.tx_hash())
+        };
+        let hash: B256 = self
+            .fixture("eth_sendTransaction", &(key,), remote)
             .await
             .map_err(EthError::OnTransact(&contract, &T::SIGNATURE))?;
-        Ok(result)
+        Ok(PendingTransactionBuilder::new(provider.root(), hash))
     }
 
     pub async fn call<T: SolCall>(
         &self,
         contract: Address,
         call: &T,
+    ) -> Result<T::Return, EthError> {
+        self.call_at(contract, call, BlockId::latest(), None).await
+    }
+
+    pub async fn call_at<T: SolCall>(
+        &self,
+        contract: Address,
+        call: &T,
+        block: BlockId,
+        overrides: Option<&StateOverride>,
     ) -> Result<T::Return, EthError> {
         let tx = TransactionRequest::default().with_call(call).to(contract);
-        let result = crate::thread::wait_timeout(self.call_timeout, self.client.call(&tx))
+        let tx = &tx;
+        // the timeout covers every attempt and the backoff between them
+        let remote = async move {
+            let attempts = self.retry.run(move || {
+                self.failover(move |provider| async move {
+                    let call = provider.call(tx).block(block);
+                    let call = match overrides {
+                        Some(overrides) => call.overrides(overrides),
+                        None => call,
+                    };
+                    Ok::<_, EthError>(call.await?)
+                })
+            });
+            wait_timeout(self.call_timeout, attempts).await?
+        };
+        let result: Bytes = self
+            .fixture("eth_call", &(tx, block, overrides), remote)
             .await
-            .map_err(EthError::OnCall(&contract, &T::SIGNATURE))?
             .map_err(EthError::OnCall(&contract, &T::SIGNATURE))?;
         let result = T::abi_decode_returns(&result, true).map_err(EthError::OnDecodeReturn(
             &contract,
@@ -151,25 +440,55 @@ impl Eth {
         //  1. block numbers may not sequential
         //  2. the types.Header.Hash() may not compatible with the chain
         let k = BlockTransactionsKind::Hashes;
-        let p = self.provider();
-        let head = p.get_block(BlockId::latest(), k).await?.unwrap();
+        let head = self.must_get_block(BlockId::latest(), k).await?;
         let hash = head.header.parent_hash;
-        let reference_block = p.get_block(hash.into(), k).await?.unwrap();
+        let reference_block = self.must_get_block(hash.into(), k).await?;
         let number = reference_block.header.number.unwrap();
         Ok((U256::from_limbs_slice(&[number]), hash))
     }
 
-    pub fn provider(&self) -> Arc<Box<dyn Provider<Http<Client>>>> {
-        self.client.clone()
+    pub fn provider(&self) -> EthProvider {
+        self.endpoints.primary().provider()
+    }
+
+    pub fn client(&self) -> &RpcClientInner<BoxTransport> {
+        self.endpoints.primary().client()
     }
 
-    pub fn client(&self) -> &RpcClientInner<Http<Client>> {
-        self.client.client()
+    pub fn endpoints(&self) -> &EndpointPool {
+        &self.endpoints
     }
 
-    pub async fn get_transaction(&self, hash: B256) -> Option<Transaction> {
-        let tx = self.client.get_transaction_by_hash(hash).await.unwrap();
-        tx
+    async fn failover<F, Fut, T>(&self, f: F) -> Result<T, EthError>
+    where
+        F: Fn(EthProvider) -> Fut,
+        Fut: Future<Output = Result<T, EthError>>,
+    {
+        let pool = &self.endpoints;
+        if pool.need_refresh_heads() {
+            pool.refresh_heads(self.call_timeout).await;
+        }
+        let mut last_err = None;
+        for idx in pool.candidates() {
+            let endpoint = &pool.endpoints()[idx];
+            pool.throttle(idx).await;
+            let start = Time::now();
+            let err = match f(endpoint.provider()).await {
+                Ok(n) => {
+                    pool.report_success(idx, Time::now().saturating_duration_since(start));
+                    return Ok(n);
+                }
+                Err(err) => err,
+            };
+            // the node did answer, the request itself is bad
+            if !self.retry.is_retryable(&err) {
+                return Err(err);
+            }
+            log::warn!(target: "eth", "endpoint {} failed: {:?}", endpoint.url(), err);
+            pool.report_failure(idx);
+            last_err = Some(EthError::OnEndpoint(endpoint.url())(err));
+        }
+        Err(last_err.unwrap())
     }
 
     pub async fn request<Params, Resp>(
@@ -181,34 +500,121 @@ impl Eth {
         Params: Serialize + Clone + std::fmt::Debug + Send + Sync + Unpin,
         Resp: Serialize + DeserializeOwned + std::fmt::Debug + Send + Sync + Unpin + 'static,
     {
-        wait_timeout(self.call_timeout, self.inner_request(method, params))
-            .await
-            .map_err(EthError::WaitResponse())?
+        self.request_with(method, params, true).await
     }
 
-    async fn inner_request<Params, Resp>(
+    pub(crate) async fn request_with<Params, Resp>(
         &self,
         method: impl Into<Cow<'static, str>>,
         params: Params,
+        cached: bool,
     ) -> Result<Resp, EthError>
     where
         Params: Serialize + Clone + std::fmt::Debug + Send + Sync + Unpin,
         Resp: Serialize + DeserializeOwned + std::fmt::Debug + Send + Sync + Unpin + 'static,
     {
         let method = method.into();
-        match &self.cache {
-            Some(cache) => {
-                let key = cache.json_key((&method, &params));
-                cache
-                    .json(&key, self.client().request(method.clone(), params))
+        let remote = {
+            let (method, params) = (&method, &params);
+            let attempts = self.retry.run(move || {
+                self.failover(move |provider| async move {
+                    let call = provider.client().request(method.clone(), params.clone());
+                    Ok::<Resp, EthError>(call.await?)
+                })
+            });
+            async move {
+                wait_timeout(self.call_timeout, attempts)
                     .await
-                    .map_err(EthError::Request(&method))
+                    .map_err(EthError::WaitResponse())?
+            }
+        };
+        let result = match self.cache_mode {
+            CacheMode::ReadThrough => {
+                let cache = self.cache.as_ref().filter(|_| cached);
+                let decision = match cache {
+                    Some(cache) => self.cache_decision(cache, &method, &params).await,
+                    None => CacheDecision::Skip,
+                };
+                match (cache, decision) {
+                    (Some(cache), CacheDecision::Store { ttl }) => {
+                        let key = cache.json_key((&method, &params));
+                        cache.json_ttl(&key, ttl, remote).await
+                    }
+                    _ => remote.await,
+                }
+            }
+            CacheMode::Record | CacheMode::Replay => self.fixture(&method, &params, remote).await,
+            CacheMode::Passthrough => remote.await,
+        };
+        result.map_err(EthError::Request(&method))
+    }
+
+    // Record and Replay for everything that reaches the node, regardless of
+    // the cache policies. `params` only has to identify the request, it is
+    // what the entry is keyed by together with `method`.
+    async fn fixture<P, V, F>(&self, method: &str, params: &P, remote: F) -> Result<V, EthError>
+    where
+        P: Serialize + std::fmt::Debug,
+        V: Serialize + DeserializeOwned,
+        F: Future<Output = Result<V, EthError>>,
+    {
+        match (self.cache_mode, &self.cache) {
+            (CacheMode::Replay, _) => self.replay(method, params),
+            (CacheMode::Record, Some(cache)) => {
+                let value = remote.await?;
+                if let Err(err) = cache.record_json(&cache.json_key((method, params)), &value) {
+                    log::warn!(target: "eth", "record {} failed: {:?}", method, err);
+                }
+                Ok(value)
             }
-            None => self
-                .client()
-                .request(method.clone(), params)
+            _ => remote.await,
+        }
+    }
+
+    fn replay<P, V>(&self, method: &str, params: &P) -> Result<V, EthError>
+    where
+        P: Serialize + std::fmt::Debug,
+        V: DeserializeOwned,
+    {
+        let value = match &self.cache {
+            Some(cache) => cache.get_json(&cache.json_key((method, params)))?,
+            None => None,
+        };
+        value.ok_or_else(|| EthError::ReplayMiss {
+            method: method.to_owned(),
+            params: param_json(params),
+        })
+    }
+
+    // a block number can only be cached once it is finalized, the finalized
+    // head is refreshed at most once per `RequestCache::with_finalized_refresh`
+    async fn cache_decision<P: Serialize>(
+        &self,
+        cache: &RequestCache,
+        method: &str,
+        params: &P,
+    ) -> CacheDecision {
+        let mut decision = cache.decide(method, params);
+        if matches!(decision, CacheDecision::NeedFinalized { .. }) && cache.finalized_outdated() {
+            match self
+                .provider()
+                .get_block_by_number(BlockNumberOrTag::Finalized, false)
                 .await
-                .map_err(EthError::Request(&method)),
+            {
+                Ok(block) => {
+                    if let Some(number) = block.and_then(|n| n.header.number) {
+                        cache.set_finalized(number);
+                    }
+                }
+                Err(err) => {
+                    log::warn!(target: "cache", "fetch finalized block failed: {:?}", err);
+                }
+            }
+            decision = cache.decide(method, params);
+        }
+        match decision {
+            CacheDecision::NeedFinalized { .. } => CacheDecision::Skip,
+            decision => decision,
         }
     }
 
@@ -220,10 +626,24 @@ impl Eth {
         method: impl Into<Cow<'static, str>>,
         params: &[Params],
         chunk_size: usize,
+    ) -> Result<Vec<Resp>, EthError> {
+        self.batch_request_chunks_with(method, params, chunk_size, true)
+            .await
+    }
+
+    pub(crate) async fn batch_request_chunks_with<
+        Params: RpcParam + std::fmt::Debug,
+        Resp: RpcReturn + Serialize,
+    >(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: &[Params],
+        chunk_size: usize,
+        cached: bool,
     ) -> Result<Vec<Resp>, EthError> {
         wait_timeout(
             self.call_timeout,
-            self.inner_batch_request_chunks(method, params, chunk_size),
+            self.inner_batch_request_chunks(method, params, chunk_size, cached),
         )
         .await
         .map_err(EthError::WaitResponse())?
@@ -237,44 +657,283 @@ impl Eth {
         method: impl Into<Cow<'static, str>>,
         params: &[Params],
         chunk_size: usize,
+        cached: bool,
     ) -> Result<Vec<Resp>, EthError> {
         let params_chunks = params.chunks(chunk_size);
         let mut out = Vec::with_capacity(params.len());
         let method = method.into();
         for p in params_chunks {
-            let resp: Vec<Resp> = self.batch_request(method.clone(), p).await?;
+            let resp: Vec<Resp> = self.batch_request_with(method.clone(), p, cached).await?;
             out.extend(resp);
         }
         Ok(out)
     }
 
+    // Sends up to `concurrency` chunks at once. The output keeps the order of
+    // `params`, and every chunk is retried on its own so a failure never
+    // refetches the chunks that already succeeded.
+    pub async fn batch_request_chunks_concurrent<Params, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: &[Params],
+        chunk_size: usize,
+        concurrency: usize,
+    ) -> Result<Vec<Resp>, EthError>
+    where
+        Params: RpcParam + std::fmt::Debug + 'static,
+        Resp: RpcReturn + Serialize,
+    {
+        self.batch_request_chunks_concurrent_with(method, params, chunk_size, concurrency, true)
+            .await
+    }
+
+    pub(crate) async fn batch_request_chunks_concurrent_with<Params, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: &[Params],
+        chunk_size: usize,
+        concurrency: usize,
+        cached: bool,
+    ) -> Result<Vec<Resp>, EthError>
+    where
+        Params: RpcParam + std::fmt::Debug + 'static,
+        Resp: RpcReturn + Serialize,
+    {
+        let method = method.into();
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut handles = Vec::new();
+        for chunk in params.chunks(chunk_size.max(1)) {
+            let eth = self.clone();
+            let method = method.clone();
+            let chunk = chunk.to_vec();
+            let semaphore = semaphore.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await.unwrap();
+                eth.batch_request_with::<Params, Resp>(method, &chunk, cached)
+                    .await
+            }));
+        }
+
+        let aborts: Vec<_> = handles.iter().map(|n| n.abort_handle()).collect();
+        let result = wait_timeout(self.call_timeout, async {
+            let mut out = Vec::with_capacity(params.len());
+            for handle in handles {
+                let resp = handle.await.map_err(EthError::BatchRequestWait())??;
+                out.extend(resp);
+            }
+            Ok::<_, EthError>(out)
+        })
+        .await
+        .map_err(EthError::WaitResponse())
+        .and_then(|n| n);
+        // a failed chunk or a timeout leaves the others running, stop them
+        if result.is_err() {
+            aborts.iter().for_each(|n| n.abort());
+        }
+        result
+    }
+
     pub async fn batch_request<Params: RpcParam + std::fmt::Debug, Resp: RpcReturn + Serialize>(
         &self,
         method: impl Into<Cow<'static, str>>,
         params: &[Params],
     ) -> Result<Vec<Resp>, EthError> {
-        wait_timeout(self.call_timeout, self.inner_batch_request(method, params))
+        self.batch_request_with(method, params, true).await
+    }
+
+    async fn batch_request_with<Params: RpcParam + std::fmt::Debug, Resp: RpcReturn + Serialize>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: &[Params],
+        cached: bool,
+    ) -> Result<Vec<Resp>, EthError> {
+        let method = method.into();
+        if self.cache_mode == CacheMode::Replay {
+            return params
+                .iter()
+                .map(|param| self.replay(&method, param))
+                .collect::<Result<_, _>>()
+                .map_err(EthError::Request(&method));
+        }
+        let (cache, decisions) = self.batch_cache_decisions(&method, params, cached).await;
+        let (method, decisions) = (&method, &decisions);
+        let attempts = self.retry.run(move || {
+            self.failover(move |provider| async move {
+                self.inner_batch_request(
+                    provider.client(),
+                    cache,
+                    decisions,
+                    method.clone(),
+                    params,
+                )
+                .await
+            })
+        });
+        wait_timeout(self.call_timeout, attempts)
             .await
             .map_err(EthError::WaitResponse())?
     }
 
+    // Like `batch_request` but a failing item does not fail the others, its
+    // error carries the index and params of the item. Only the items that
+    // succeeded are cached, so calling again with the failed params is cheap.
+    pub async fn batch_request_results<Params, Resp>(
+        &self,
+        method: impl Into<Cow<'static, str>>,
+        params: &[Params],
+    ) -> Result<Vec<Result<Resp, EthError>>, EthError>
+    where
+        Params: RpcParam + std::fmt::Debug,
+        Resp: RpcReturn + Serialize,
+    {
+        let method = method.into();
+        if self.cache_mode == CacheMode::Replay {
+            let results = params.iter().enumerate().map(|(idx, param)| {
+                self.replay(&method, param)
+                    .map_err(EthError::BatchItem(&idx, &param_json(param)))
+            });
+            return Ok(results.collect());
+        }
+        let (cache, decisions) = self.batch_cache_decisions(&method, params, true).await;
+        let (method, decisions) = (&method, &decisions);
+        let attempts = self.retry.run(move || {
+            self.failover(move |provider| async move {
+                self.inner_batch_results(
+                    provider.client(),
+                    cache,
+                    decisions,
+                    method.clone(),
+                    params,
+                )
+                .await
+            })
+        });
+        wait_timeout(self.call_timeout, attempts)
+            .await
+            .map_err(EthError::WaitResponse())?
+    }
+
+    async fn batch_cache_decisions<Params: Serialize>(
+        &self,
+        method: &str,
+        params: &[Params],
+        cached: bool,
+    ) -> (Option<&RequestCache>, Vec<CacheDecision>) {
+        let cache = match self.cache_mode {
+            CacheMode::ReadThrough if cached => self.cache.as_ref(),
+            CacheMode::Record => self.cache.as_ref(),
+            _ => None,
+        };
+        let mut decisions = Vec::with_capacity(params.len());
+        for param in params {
+            decisions.push(match cache {
+                Some(_) if self.cache_mode == CacheMode::Record => {
+                    CacheDecision::Store { ttl: None }
+                }
+                Some(cache) => self.cache_decision(cache, method, param).await,
+                None => CacheDecision::Skip,
+            });
+        }
+        (cache, decisions)
+    }
+
+    async fn inner_batch_results<
+        Params: RpcParam + std::fmt::Debug,
+        Resp: RpcReturn + Serialize,
+    >(
+        &self,
+        client: &RpcClientInner<BoxTransport>,
+        cache: Option<&RequestCache>,
+        decisions: &[CacheDecision],
+        method: Cow<'static, str>,
+        params: &[Params],
+    ) -> Result<Vec<Result<Resp, EthError>>, EthError> {
+        let recording = self.cache_mode == CacheMode::Record;
+        let mut results: Vec<Option<Result<Resp, EthError>>> = Vec::with_capacity(params.len());
+        for (param, decision) in params.iter().zip(decisions) {
+            results.push(match (cache, decision) {
+                (Some(cache), CacheDecision::Store { .. }) if !recording => cache
+                    .get_json(&cache.json_key((method.clone(), param)))?
+                    .map(Ok),
+                _ => None,
+            });
+        }
+
+        let mut batch = BatchRequest::new(client);
+        let mut waiters = Vec::new();
+        for (idx, param) in params.iter().enumerate() {
+            if results[idx].is_some() {
+                continue;
+            }
+            waiters.push((
+                param,
+                idx,
+                batch
+                    .add_call::<_, Resp>(method.clone(), param)
+                    .map_err(EthError::BatchRequestSerFail())?,
+            ));
+        }
+
+        if !waiters.is_empty() {
+            batch.send().await.map_err(EthError::BatchSend())?;
+            wait_timeout(self.call_timeout, async {
+                for (p, idx, waiter) in waiters {
+                    let result = match waiter.await {
+                        Ok(result) => result,
+                        Err(err) => {
+                            let err = EthError::BatchItem(&idx, &param_json(p))(err);
+                            results[idx] = Some(Err(err));
+                            continue;
+                        }
+                    };
+                    if let (Some(cache), CacheDecision::Store { ttl }) = (cache, decisions[idx]) {
+                        let key = cache.json_key((method.clone(), p));
+                        let saved = match recording {
+                            true => cache.record_json(&key, &result),
+                            false => cache.save_json_ttl(&key, &result, ttl),
+                        };
+                        // the node already answered, a full disk must not fail the request
+                        if let Err(err) = saved {
+                            log::warn!(target: "eth", "cache {} result failed: {:?}", method, err);
+                        }
+                    }
+                    results[idx] = Some(Ok(result));
+                }
+                Ok::<(), EthError>(())
+            })
+            .await
+            .map_err(EthError::BatchRequestWait())?
+            .map_err(EthError::BatchRequestWait())?;
+        }
+
+        Ok(results.into_iter().map(|n| n.unwrap()).collect())
+    }
+
     async fn inner_batch_request<
         Params: RpcParam + std::fmt::Debug,
         Resp: RpcReturn + Serialize,
     >(
         &self,
+        client: &RpcClientInner<BoxTransport>,
+        cache: Option<&RequestCache>,
+        decisions: &[CacheDecision],
         method: impl Into<Cow<'static, str>>,
         params: &[Params],
     ) -> Result<Vec<Resp>, EthError> {
         let method: Cow<'static, str> = method.into();
-        let mut batch = BatchRequest::new(self.client());
+        let mut batch = BatchRequest::new(client);
         let mut waiters = Vec::new();
-        let mut cached_result: Vec<Option<Resp>> = match &self.cache {
-            Some(cache) => cache
-                .batch_json(params.iter().map(|p| (method.clone(), p)))
-                .map_err(EthError::BatchRequestDerRespFail())?,
-            None => params.iter().map(|_| None).collect(),
-        };
+        // recording always asks the node so the fixtures reflect it
+        let recording = self.cache_mode == CacheMode::Record;
+        let mut cached_result: Vec<Option<Resp>> = Vec::with_capacity(params.len());
+        for (param, decision) in params.iter().zip(decisions) {
+            cached_result.push(match (cache, decision) {
+                (Some(cache), CacheDecision::Store { .. }) if !recording => {
+                    cache.get_json(&cache.json_key((method.clone(), param)))?
+                }
+                _ => None,
+            });
+        }
         for (idx, param) in params.into_iter().enumerate() {
             if cached_result[idx].is_some() {
                 continue;
@@ -293,9 +952,16 @@ impl Eth {
             wait_timeout(self.call_timeout, async {
                 for (p, idx, waiter) in waiters {
                     let result = waiter.await.map_err(EthError::BatchRequestDerRespFail())?;
-                    if let Some(cache) = &self.cache {
+                    if let (Some(cache), CacheDecision::Store { ttl }) = (cache, decisions[idx]) {
                         let key = cache.json_key((method.clone(), p));
-                        cache.save_json(&key, &result).unwrap();
+                        let saved = match recording {
+                            true => cache.record_json(&key, &result),
+                            false => cache.save_json_ttl(&key, &result, ttl),
+                        };
+                        // the node already answered, a full disk must not fail the request
+                        if let Err(err) = saved {
+                            log::warn!(target: "eth", "cache {} result failed: {:?}", method, err);
+                        }
                     }
                     cached_result[idx] = Some(result);
                 }
@@ -309,3 +975,181 @@ impl Eth {
         Ok(cached_result.into_iter().map(|n| n.unwrap()).collect())
     }
 }
+
+fn param_json<P: Serialize + std::fmt::Debug>(params: &P) -> String {
+    serde_json::to_string(params).unwrap_or_else(|_| format!("{:?}", params))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    };
+
+    use alloy::primitives::U64;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::eth::mock_rpc::{mock_rpc_server, MockResponse};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("base-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn test_replay_without_node() {
+        let dir = temp_dir("eth-record");
+        let (url, hits) = mock_rpc_server(|_, req| MockResponse::result(req, "0x10"));
+        let mut eth = Eth::dial(&url, None).unwrap();
+        eth.with_cache(CacheBackend::Files(dir.clone()))
+            .unwrap()
+            .with_cache_mode(CacheMode::Record);
+        let number: U64 = eth.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(number, U64::from(0x10));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // nothing listens on port 1, the answer can only come from the fixture
+        let mut eth = Eth::dial("http://127.0.0.1:1", None).unwrap();
+        eth.with_cache(CacheBackend::Files(dir.clone()))
+            .unwrap()
+            .with_cache_mode(CacheMode::Replay);
+        let number: U64 = eth.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(number, U64::from(0x10));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_replay_miss() {
+        let dir = temp_dir("eth-replay-miss");
+        let (url, hits) = mock_rpc_server(|_, req| MockResponse::result(req, "0x10"));
+        let mut eth = Eth::dial(&url, None).unwrap();
+        eth.with_cache(CacheBackend::Files(dir.clone()))
+            .unwrap()
+            .with_cache_mode(CacheMode::Replay);
+
+        let err = eth
+            .request::<_, U64>("eth_blockNumber", ())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err.origin(), EthError::ReplayMiss { method, .. } if method == "eth_blockNumber"),
+            "{:?}",
+            err
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // the first param of the first call, which identifies the chunk
+    fn first_param(req: &Value) -> u64 {
+        req[0]["params"][0].as_u64().unwrap()
+    }
+
+    fn echo(call: &Value) -> Value {
+        format!("{:#x}", call["params"][0].as_u64().unwrap()).into()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chunks() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let (url, hits) = mock_rpc_server({
+            let (in_flight, max_in_flight) = (in_flight.clone(), max_in_flight.clone());
+            move |_, req| {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(now, Ordering::SeqCst);
+                // later chunks answer first, the output must not follow them
+                let delay = 100 - first_param(req) * 10;
+                std::thread::sleep(Duration::from_millis(delay));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                MockResponse::batch(req, echo)
+            }
+        });
+        let eth = Eth::dial(&url, None).unwrap();
+
+        let params: Vec<[u64; 1]> = (0..8).map(|n| [n]).collect();
+        let result: Vec<U64> = eth
+            .batch_request_chunks_concurrent_with("test_echo", &params, 2, 2, false)
+            .await
+            .unwrap();
+        assert_eq!(result, (0..8).map(U64::from).collect::<Vec<_>>());
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chunks_abort_on_error() {
+        let (url, hits) = mock_rpc_server(|_, req| match first_param(req) {
+            0 => MockResponse::status(400, None),
+            _ => {
+                std::thread::sleep(Duration::from_millis(500));
+                MockResponse::batch(req, echo)
+            }
+        });
+        let eth = Eth::dial(&url, None).unwrap();
+
+        let now = Instant::now();
+        let params: Vec<[u64; 1]> = (0..12).map(|n| [n]).collect();
+        let result = eth
+            .batch_request_chunks_concurrent_with::<_, U64>("test_echo", &params, 2, 2, false)
+            .await;
+        assert!(result.is_err());
+        assert!(now.elapsed() < Duration::from_millis(500));
+
+        // the failed chunk frees a permit the next chunk may take before the
+        // abort, every later one is stopped while still waiting for a permit
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        assert!(hits.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch_results_keep_neighbours() {
+        let (url, _) = mock_rpc_server(|_, req| {
+            let body: Vec<Value> = req
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|call| match call["params"][0].as_u64().unwrap() {
+                    1 => serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": call["id"],
+                        "error": {"code": -32000, "message": "execution reverted"},
+                    }),
+                    _ => serde_json::json!({"jsonrpc": "2.0", "id": call["id"], "result": echo(call)}),
+                })
+                .collect();
+            MockResponse {
+                status: 200,
+                retry_after: None,
+                body: Value::Array(body).to_string(),
+            }
+        });
+        let eth = Eth::dial(&url, None).unwrap();
+
+        let params: Vec<[u64; 1]> = (0..3).map(|n| [n]).collect();
+        let results: Vec<Result<U64, EthError>> = eth
+            .batch_request_results("test_echo", &params)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &U64::from(0));
+        assert_eq!(results[2].as_ref().unwrap(), &U64::from(2));
+
+        let err = results[1].as_ref().unwrap_err();
+        match err {
+            EthError::Stack { stack, .. } => assert!(stack.iter().any(|n| matches!(
+                n,
+                EthErrorStack::BatchItem { index: 1, params } if params == "[1]"
+            ))),
+            err => panic!("missing BatchItem frame: {:?}", err),
+        }
+        match err.origin() {
+            EthError::Rpc(RpcError::ErrorResp(payload)) => assert_eq!(payload.code, -32000),
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use alloy::{
+    eips::BlockNumberOrTag, primitives::U256, providers::Provider, rpc::types::TransactionRequest,
+    transports::BoxTransport,
+};
+
+use super::EthError;
+
+#[derive(Clone, Debug, Default)]
+pub enum FeeStrategy {
+    // whatever `estimate_eip1559_fees` returns
+    #[default]
+    Recommended,
+    Fixed {
+        max_fee_per_gas: u128,
+        max_priority_fee_per_gas: u128,
+    },
+    // priority fee from the given percentile of the recent `eth_feeHistory` rewards
+    Percentile {
+        blocks: u64,
+        percentile: f64,
+        base_fee_multiplier_percent: u128,
+    },
+    CappedMultiplier {
+        multiplier_percent: u128,
+        max_fee_cap: u128,
+        max_priority_fee_cap: u128,
+    },
+    // for chains without eip-1559
+    Legacy {
+        multiplier_percent: u128,
+        max_gas_price: Option<u128>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct GasLimitPolicy {
+    pub multiplier_percent: u128,
+    pub extra: u128,
+    pub max: Option<u128>,
+}
+
+impl Default for GasLimitPolicy {
+    fn default() -> Self {
+        Self {
+            multiplier_percent: 100,
+            extra: 0,
+            max: None,
+        }
+    }
+}
+
+impl GasLimitPolicy {
+    pub fn padded(multiplier_percent: u128) -> Self {
+        Self {
+            multiplier_percent,
+            ..Default::default()
+        }
+    }
+
+    pub fn apply(&self, estimated: u128) -> u128 {
+        let gas = estimated * self.multiplier_percent / 100 + self.extra;
+        match self.max {
+            Some(max) => gas.min(max),
+            None => gas,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub gas_limit: u128,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub gas_price: Option<u128>,
+}
+
+impl FeeEstimate {
+    pub fn max_cost(&self) -> U256 {
+        let price = self.max_fee_per_gas.or(self.gas_price).unwrap_or_default();
+        U256::from(self.gas_limit) * U256::from(price)
+    }
+
+    pub fn apply(&self, request: &mut TransactionRequest) {
+        request.gas = Some(self.gas_limit);
+        request.max_fee_per_gas = self.max_fee_per_gas;
+        request.max_priority_fee_per_gas = self.max_priority_fee_per_gas;
+        request.gas_price = self.gas_price;
+    }
+}
+
+impl FeeStrategy {
+    pub async fn estimate(
+        &self,
+        provider: &dyn Provider<BoxTransport>,
+        estimate: &mut FeeEstimate,
+    ) -> Result<(), EthError> {
+        match self {
+            Self::Recommended => {
+                let fees = provider.estimate_eip1559_fees(None).await?;
+                estimate.max_fee_per_gas = Some(fees.max_fee_per_gas);
+                estimate.max_priority_fee_per_gas = Some(fees.max_priority_fee_per_gas);
+            }
+            Self::Fixed {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => {
+                estimate.max_fee_per_gas = Some(*max_fee_per_gas);
+                estimate.max_priority_fee_per_gas = Some(*max_priority_fee_per_gas);
+            }
+            Self::Percentile {
+                blocks,
+                percentile,
+                base_fee_multiplier_percent,
+            } => {
+                let history = provider
+                    .get_fee_history(*blocks, BlockNumberOrTag::Latest, &[*percentile])
+                    .await?;
+                let base_fee = history
+                    .next_block_base_fee()
+                    .or_else(|| history.latest_block_base_fee())
+                    .unwrap_or_default();
+                let mut rewards: Vec<u128> = history
+                    .reward
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|n| n.first().cloned())
+                    .filter(|n| *n > 0)
+                    .collect();
+                rewards.sort();
+                let priority_fee = rewards.get(rewards.len() / 2).cloned().unwrap_or_default();
+                estimate.max_fee_per_gas =
+                    Some(base_fee * base_fee_multiplier_percent / 100 + priority_fee);
+                estimate.max_priority_fee_per_gas = Some(priority_fee);
+            }
+            Self::CappedMultiplier {
+                multiplier_percent,
+                max_fee_cap,
+                max_priority_fee_cap,
+            } => {
+                let fees = provider.estimate_eip1559_fees(None).await?;
+                let max_fee = (fees.max_fee_per_gas * multiplier_percent / 100).min(*max_fee_cap);
+                let priority_fee = (fees.max_priority_fee_per_gas * multiplier_percent / 100)
+                    .min(*max_priority_fee_cap)
+                    .min(max_fee);
+                estimate.max_fee_per_gas = Some(max_fee);
+                estimate.max_priority_fee_per_gas = Some(priority_fee);
+            }
+            Self::Legacy {
+                multiplier_percent,
+                max_gas_price,
+            } => {
+                let gas_price = provider.get_gas_price().await? * multiplier_percent / 100;
+                estimate.gas_price = Some(match max_gas_price {
+                    Some(max) => gas_price.min(*max),
+                    None => gas_price,
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, Bytes, B256, U256},
+    rpc::types::{
+        Block, BlockTransactionsKind, EIP1186AccountProofResponse, Filter, Log, Transaction,
+        TransactionReceipt,
+    },
+};
+
+use super::{Eth, EthError};
+
+impl Eth {
+    pub async fn get_block(
+        &self,
+        block: BlockId,
+        kind: BlockTransactionsKind,
+    ) -> Result<Option<Block>, EthError> {
+        let full = matches!(kind, BlockTransactionsKind::Full);
+        // the cache policy decides whether the block is stable enough to keep
+        let result = match block {
+            BlockId::Hash(hash) => {
+                self.request("eth_getBlockByHash", (hash.block_hash, full))
+                    .await
+            }
+            BlockId::Number(number) => self.request("eth_getBlockByNumber", (number, full)).await,
+        };
+        result.map_err(EthError::GetBlock(&block))
+    }
+
+    pub async fn must_get_block(
+        &self,
+        block: BlockId,
+        kind: BlockTransactionsKind,
+    ) -> Result<Block, EthError> {
+        match self.get_block(block, kind).await? {
+            Some(block) => Ok(block),
+            None => Err(EthError::BlockNotFound(block)),
+        }
+    }
+
+    pub async fn get_transaction(&self, hash: B256) -> Result<Option<Transaction>, EthError> {
+        self.request_with("eth_getTransactionByHash", (hash,), false)
+            .await
+            .map_err(EthError::GetTransaction(&hash))
+    }
+
+    pub async fn get_receipt(&self, hash: B256) -> Result<Option<TransactionReceipt>, EthError> {
+        self.request_with("eth_getTransactionReceipt", (hash,), false)
+            .await
+            .map_err(EthError::GetReceipt(&hash))
+    }
+
+    pub async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>, EthError> {
+        self.request("eth_getLogs", (filter.clone(),))
+            .await
+            .map_err(EthError::GetLogsFilter(filter))
+    }
+
+    pub async fn get_code(&self, address: Address, block: BlockId) -> Result<Bytes, EthError> {
+        self.request("eth_getCode", (address, block))
+            .await
+            .map_err(EthError::GetCode(&address, &block))
+    }
+
+    pub async fn get_storage_at(
+        &self,
+        address: Address,
+        slot: U256,
+        block: BlockId,
+    ) -> Result<U256, EthError> {
+        self.request("eth_getStorageAt", (address, slot, block))
+            .await
+            .map_err(EthError::GetStorageAt(&address, &slot, &block))
+    }
+
+    pub async fn get_proof(
+        &self,
+        address: Address,
+        keys: Vec<B256>,
+        block: BlockId,
+    ) -> Result<EIP1186AccountProofResponse, EthError> {
+        self.request("eth_getProof", (address, keys, block))
+            .await
+            .map_err(EthError::GetProof(&address, &block))
+    }
+}
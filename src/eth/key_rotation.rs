@@ -0,0 +1,328 @@
+use std::{collections::VecDeque, future::Future, pin::Pin, sync::Mutex, time::Duration};
+
+use alloy::primitives::{Address, U256};
+use tokio::sync::broadcast;
+
+use crate::{time::Time, trace::Alive};
+
+use super::{EthError, Keypair};
+
+// events kept for a subscriber that falls behind, older ones are skipped
+const EVENT_CAPACITY: usize = 16;
+
+type RegisterFn = Box<
+    dyn Fn(Keypair) -> Pin<Box<dyn Future<Output = Result<U256, EthError>> + Send>> + Send + Sync,
+>;
+
+#[derive(Clone, Debug)]
+pub enum RotationEvent {
+    Rotated {
+        previous: Address,
+        current: Address,
+        instance_id: U256,
+    },
+    // the candidate key was dropped, the current key stays in use
+    Failed {
+        candidate: Address,
+        error: String,
+    },
+    // a previous key left its grace period and no longer signs
+    Expired {
+        address: Address,
+    },
+}
+
+// Rotates a `Keypair` in place. The new key is only committed after the
+// registration callback returned its instance id, a failed registration leaves
+// the current key untouched. Previous keys stay available for `grace_period`
+// so signatures already in flight can still be produced and verified.
+pub struct KeyRotation {
+    keypair: Keypair,
+    register: RegisterFn,
+    interval: Duration,
+    grace_period: Duration,
+    retry_delay: Duration,
+    // (key, retired at), oldest first
+    retired: Mutex<VecDeque<(Keypair, Time)>>,
+    last_rotation: Mutex<Time>,
+    rotating: tokio::sync::Mutex<()>,
+    events: broadcast::Sender<RotationEvent>,
+}
+
+impl KeyRotation {
+    // `register` receives the uncommitted key, e.g. to submit its attestation
+    // through `Eth::transact`, and returns the instance id to commit with
+    pub fn new<F, Fut>(keypair: &Keypair, register: F) -> Self
+    where
+        F: Fn(Keypair) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<U256, EthError>> + Send + 'static,
+    {
+        Self {
+            keypair: keypair.clone(),
+            register: Box::new(move |kp| Box::pin(register(kp))),
+            interval: Duration::from_secs(24 * 3600),
+            grace_period: Duration::from_secs(600),
+            retry_delay: Duration::from_secs(60),
+            retired: Mutex::new(VecDeque::new()),
+            last_rotation: Mutex::new(Time::now()),
+            rotating: tokio::sync::Mutex::new(()),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    // wait before trying again after a failed registration
+    pub fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
+    // every subscriber receives every event
+    pub fn subscribe(&self) -> broadcast::Receiver<RotationEvent> {
+        self.events.subscribe()
+    }
+
+    fn dispatch(&self, event: RotationEvent) {
+        // only fails without subscribers
+        let _ = self.events.send(event);
+    }
+
+    pub async fn rotate(&self) -> Result<U256, EthError> {
+        let _guard = self.rotating.lock().await;
+        let rotate = self.keypair.rotate();
+        let candidate = rotate.address();
+        let instance_id = match (self.register)(Keypair::clone(&rotate)).await {
+            Ok(instance_id) => instance_id,
+            Err(err) => {
+                log::warn!(target: "eth", "register key {} failed: {:?}", candidate, err);
+                self.dispatch(RotationEvent::Failed {
+                    candidate,
+                    error: format!("{:?}", err),
+                });
+                return Err(err);
+            }
+        };
+
+        // a detached copy, the shared `Keypair` switches over on commit
+        let previous = Keypair::from_parts(self.keypair.instance_id(), *self.keypair.secret_key());
+        rotate.commit(instance_id);
+        let now = Time::now();
+        *self.last_rotation.lock().unwrap() = now;
+        self.retired
+            .lock()
+            .unwrap()
+            .push_back((previous.clone(), now));
+
+        self.dispatch(RotationEvent::Rotated {
+            previous: previous.address(),
+            current: self.keypair.address(),
+            instance_id,
+        });
+        Ok(instance_id)
+    }
+
+    // keys retired at or before it are expired, None when the grace period
+    // reaches back before the epoch and every key is kept
+    fn grace_deadline(&self) -> Option<Time> {
+        Time::now().checked_sub(self.grace_period)
+    }
+
+    // previous keys still inside their grace period, newest first
+    pub fn previous_keys(&self) -> Vec<Keypair> {
+        let deadline = self.grace_deadline();
+        let retired = self.retired.lock().unwrap();
+        retired
+            .iter()
+            .rev()
+            .filter(|(_, retired_at)| deadline.map_or(true, |n| *retired_at > n))
+            .map(|(kp, _)| kp.clone())
+            .collect()
+    }
+
+    // the key that should sign for `address`, either the current one or a
+    // previous one inside its grace period
+    pub fn signer_for(&self, address: Address) -> Option<Keypair> {
+        if self.keypair.address() == address {
+            return Some(self.keypair.clone());
+        }
+        self.previous_keys()
+            .into_iter()
+            .find(|kp| kp.address() == address)
+    }
+
+    pub fn is_valid_signer(&self, address: Address) -> bool {
+        self.signer_for(address).is_some()
+    }
+
+    fn prune(&self) {
+        let Some(deadline) = self.grace_deadline() else {
+            return;
+        };
+        let expired: Vec<_> = {
+            let mut retired = self.retired.lock().unwrap();
+            let mut expired = Vec::new();
+            while let Some((kp, retired_at)) = retired.front() {
+                if *retired_at > deadline {
+                    break;
+                }
+                expired.push(kp.address());
+                retired.pop_front();
+            }
+            expired
+        };
+        for address in expired {
+            self.dispatch(RotationEvent::Expired { address });
+        }
+    }
+
+    // None when the interval is too long to ever come due
+    fn next_rotation(&self, retry_at: Option<Time>) -> Option<Time> {
+        let due = self
+            .last_rotation
+            .lock()
+            .unwrap()
+            .checked_add(self.interval)?;
+        Some(retry_at.map_or(due, |retry_at| due.max(retry_at)))
+    }
+
+    // rotates every `interval` and expires previous keys until the alive is
+    // shut down, a manual `rotate` restarts the interval
+    pub async fn run(&self, alive: &Alive) {
+        let mut retry_at: Option<Time> = None;
+        loop {
+            let expiry = self.retired.lock().unwrap().front().map(|n| n.1);
+            let expiry = expiry.and_then(|n| n.checked_add(self.grace_period));
+            let wake = match (self.next_rotation(retry_at), expiry) {
+                (Some(due), Some(expiry)) => Some(due.min(expiry)),
+                (due, expiry) => due.or(expiry),
+            };
+            let sleep = match wake {
+                Some(wake) => {
+                    alive
+                        .sleep(wake.saturating_duration_since(Time::now()))
+                        .await
+                }
+                None => {
+                    alive.wait_shutdown().await;
+                    false
+                }
+            };
+            if !sleep {
+                break;
+            }
+
+            self.prune();
+            match self.next_rotation(retry_at) {
+                Some(due) if Time::now() >= due => {}
+                _ => continue,
+            }
+            retry_at = match self.rotate().await {
+                Ok(_) => None,
+                Err(_) => Some(Time::now().saturating_add(self.retry_delay)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotation() -> KeyRotation {
+        KeyRotation::new(&Keypair::new(), |_| async { Ok(U256::from(7)) })
+    }
+
+    #[tokio::test]
+    async fn test_rotate_keeps_previous_key() {
+        let rotation = rotation();
+        let previous = rotation.keypair().address();
+        let mut events = rotation.subscribe();
+        let mut other = rotation.subscribe();
+
+        assert_eq!(rotation.rotate().await.unwrap(), U256::from(7));
+        let current = rotation.keypair().address();
+        assert_ne!(current, previous);
+        assert_eq!(rotation.keypair().instance_id(), Some(U256::from(7)));
+        assert!(rotation.is_valid_signer(current));
+        assert!(rotation.is_valid_signer(previous));
+
+        // every subscriber sees the event, not only the first one
+        for events in [&mut events, &mut other] {
+            match events.try_recv().unwrap() {
+                RotationEvent::Rotated {
+                    previous: from,
+                    current: to,
+                    instance_id,
+                } => {
+                    assert_eq!((from, to), (previous, current));
+                    assert_eq!(instance_id, U256::from(7));
+                }
+                event => panic!("unexpected event: {:?}", event),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_registration() {
+        let keypair = Keypair::new();
+        let address = keypair.address();
+        let rotation = KeyRotation::new(&keypair, |_| async { Err(EthError::NoEndpoint) });
+        let mut events = rotation.subscribe();
+
+        assert!(rotation.rotate().await.is_err());
+        assert_eq!(rotation.keypair().address(), address);
+        assert!(rotation.previous_keys().is_empty());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            RotationEvent::Failed { candidate, .. } if candidate != address
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_prune_after_grace_period() {
+        let rotation = rotation().with_grace_period(Duration::from_millis(50));
+        let previous = rotation.keypair().address();
+        rotation.rotate().await.unwrap();
+        let mut events = rotation.subscribe();
+
+        rotation.prune();
+        assert_eq!(rotation.previous_keys().len(), 1);
+        assert!(events.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!rotation.is_valid_signer(previous));
+        rotation.prune();
+        assert!(rotation.retired.lock().unwrap().is_empty());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            RotationEvent::Expired { address } if address == previous
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_durations() {
+        let rotation = rotation()
+            .with_interval(Duration::MAX)
+            .with_grace_period(Duration::MAX);
+        let previous = rotation.keypair().address();
+        assert_eq!(rotation.next_rotation(None), None);
+
+        rotation.rotate().await.unwrap();
+        rotation.prune();
+        assert!(rotation.is_valid_signer(previous));
+        assert_eq!(rotation.previous_keys().len(), 1);
+    }
+}
@@ -1,16 +1,26 @@
 use std::sync::{Arc, Mutex};
 
-use alloy::primitives::{keccak256, Address, U256};
-use secp256k1::{
-    ecdsa::{RecoverableSignature, RecoveryId},
-    rand::thread_rng,
-    Message, PublicKey, SECP256K1,
+use alloy::{
+    hex,
+    primitives::{eip191_hash_message, keccak256, Address, U256},
+    signers::local::PrivateKeySigner,
+    sol_types::{Eip712Domain, SolStruct},
 };
+use sec1::{
+    der::{asn1::ObjectIdentifier, pem::LineEnding, Decode, Encode, EncodePem},
+    EcParameters, EcPrivateKey,
+};
+use secp256k1::{rand::thread_rng, Message, PublicKey, SECP256K1};
 
 pub use secp256k1::SecretKey;
 
 use crate::stack_error;
 
+use super::SignatureVerifier;
+
+const SECP256K1_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.10");
+const SEC1_PEM_LABEL: &str = "EC PRIVATE KEY";
+
 #[derive(Clone, Debug)]
 pub struct Keypair {
     key: Arc<Mutex<(Option<U256>, Arc<SecretKey>, Arc<PublicKey>)>>,
@@ -24,11 +34,107 @@ impl Keypair {
         }
     }
 
+    pub(crate) fn from_parts(instance_id: Option<U256>, sk: SecretKey) -> Self {
+        let pk = sk.public_key(SECP256K1);
+        Self {
+            key: Arc::new(Mutex::new((instance_id, Arc::new(sk), Arc::new(pk)))),
+        }
+    }
+
+    pub fn from_secret_key(sk: SecretKey) -> Self {
+        Self::from_parts(None, sk)
+    }
+
+    // with or without the 0x prefix
+    pub fn from_hex(data: &str) -> Result<Self, KeypairError> {
+        let data = hex::decode(data.trim())
+            .map_err(|err| KeypairError::InvalidSecretKey(format!("{}", err)))?;
+        Ok(Self::from_secret_key(SecretKey::from_slice(&data)?))
+    }
+
+    // SEC1 `ECPrivateKey`, as written by `openssl ecparam -name secp256k1`
+    pub fn from_sec1_der(der: &[u8]) -> Result<Self, KeypairError> {
+        let key = EcPrivateKey::from_der(der)
+            .map_err(|err| KeypairError::InvalidSecretKey(format!("{}", err)))?;
+        if let Some(parameters) = key.parameters {
+            if parameters.named_curve() != Some(SECP256K1_OID) {
+                return Err(KeypairError::InvalidSecretKey(format!(
+                    "unexpected curve {:?}",
+                    parameters
+                )));
+            }
+        }
+        Ok(Self::from_secret_key(SecretKey::from_slice(
+            key.private_key,
+        )?))
+    }
+
+    pub fn from_sec1_pem(pem: &str) -> Result<Self, KeypairError> {
+        let (label, der) = sec1::der::pem::decode_vec(pem.trim().as_bytes())
+            .map_err(|err| KeypairError::InvalidSecretKey(format!("{}", err)))?;
+        if label != SEC1_PEM_LABEL {
+            return Err(KeypairError::InvalidSecretKey(format!(
+                "unexpected pem label {}",
+                label
+            )));
+        }
+        Self::from_sec1_der(&der)
+    }
+
+    pub fn from_signer(signer: &PrivateKeySigner) -> Self {
+        let sk = SecretKey::from_slice(signer.to_bytes().as_slice())
+            .expect("a signing key is a valid secp256k1 secret key");
+        Self::from_secret_key(sk)
+    }
+
+    // 0x prefixed
+    pub fn to_hex(&self) -> String {
+        hex::encode_prefixed(self.secret_key().secret_bytes())
+    }
+
+    // includes the curve and the public key so openssl can read it back
+    pub fn to_sec1_der(&self) -> Result<Vec<u8>, KeypairError> {
+        self.with_sec1(|key| key.to_der())
+    }
+
+    pub fn to_sec1_pem(&self) -> Result<String, KeypairError> {
+        self.with_sec1(|key| key.to_pem(LineEnding::LF))
+    }
+
+    fn with_sec1<T>(
+        &self,
+        f: impl FnOnce(EcPrivateKey) -> sec1::der::Result<T>,
+    ) -> Result<T, KeypairError> {
+        let secret = self.secret_key().secret_bytes();
+        let public = self.public_key_uncompressed();
+        f(EcPrivateKey {
+            private_key: &secret,
+            parameters: Some(EcParameters::NamedCurve(SECP256K1_OID)),
+            public_key: Some(&public),
+        })
+        .map_err(|err| KeypairError::InvalidSecretKey(format!("{}", err)))
+    }
+
+    // drives `Eth` with the same identity, it does not follow rotations
+    pub fn to_signer(&self) -> PrivateKeySigner {
+        let bytes = self.secret_key().secret_bytes();
+        PrivateKeySigner::from_bytes(&bytes.into())
+            .expect("a secp256k1 secret key is a valid signing key")
+    }
+
+    pub fn public_key_compressed(&self) -> [u8; 33] {
+        self.public_key().serialize()
+    }
+
+    pub fn public_key_uncompressed(&self) -> [u8; 65] {
+        self.public_key().serialize_uncompressed()
+    }
+
     pub fn address(&self) -> Address {
         Self::public_key_to_address(&self.public_key())
     }
 
-    fn public_key_to_address(pk: &PublicKey) -> Address {
+    pub(crate) fn public_key_to_address(pk: &PublicKey) -> Address {
         let hash = keccak256(&pk.serialize_uncompressed()[1..]);
         Address::from_slice(&hash[12..])
     }
@@ -68,16 +174,35 @@ impl Keypair {
         sig
     }
 
+    // EIP-191 personal_sign
+    pub fn sign_message(&self, message: impl AsRef<[u8]>) -> [u8; 65] {
+        Self::sign_digest_ecdsa(&self.secret_key(), eip191_hash_message(message).0)
+    }
+
+    // EIP-712 typed data, `value` is usually a `sol!` struct
+    pub fn sign_typed_data<T: SolStruct>(&self, value: &T, domain: &Eip712Domain) -> [u8; 65] {
+        Self::sign_digest_ecdsa(&self.secret_key(), value.eip712_signing_hash(domain).0)
+    }
+
+    // v may be 0/1, 27/28 or EIP-155 encoded, high-s signatures are rejected,
+    // see `SignatureVerifier` for the knobs
     pub fn recover(digest: [u8; 32], sig: [u8; 65]) -> Result<Address, KeypairError> {
-        let msg = Message::from_digest(digest);
-        let recid =
-            RecoveryId::from_i32(sig[64] as _).map_err(KeypairError::InvalidRecoverId(&sig[64]))?;
-        let rec_sig = RecoverableSignature::from_compact(&sig[..64], recid)
-            .map_err(KeypairError::FromCompact(&sig))?;
-        let key = SECP256K1
-            .recover_ecdsa(&msg, &rec_sig)
-            .map_err(KeypairError::RecoverSig(&sig))?;
-        Ok(Self::public_key_to_address(&key))
+        SignatureVerifier::default().recover(digest, sig)
+    }
+
+    pub fn recover_message(
+        message: impl AsRef<[u8]>,
+        sig: [u8; 65],
+    ) -> Result<Address, KeypairError> {
+        Self::recover(eip191_hash_message(message).0, sig)
+    }
+
+    pub fn recover_typed_data<T: SolStruct>(
+        value: &T,
+        domain: &Eip712Domain,
+        sig: [u8; 65],
+    ) -> Result<Address, KeypairError> {
+        Self::recover(value.eip712_signing_hash(domain).0, sig)
     }
 }
 
@@ -86,6 +211,12 @@ pub struct KeypairRotate<'a> {
     old_key: &'a Keypair,
 }
 
+impl From<SecretKey> for Keypair {
+    fn from(sk: SecretKey) -> Self {
+        Self::from_secret_key(sk)
+    }
+}
+
 impl<'a> KeypairRotate<'a> {
     pub fn commit(self, instance_id: U256) {
         let mut new_key = self.kp.key.lock().unwrap().clone();
@@ -106,13 +237,116 @@ stack_error! {
     name: KeypairError,
     stack_name: KeypairErrorStack,
     error: {
+        InvalidKeystore(String),
+        UnsupportedKeystore(String),
+        KeystoreMacMismatch,
+        AddressMismatch { expected: Address, got: Address },
+        Seal(String),
+        InvalidSecretKey(String),
+        InvalidV(u64),
+        HighS,
+        ChainIdMismatch { expected: u64, got: u64 },
     },
     wrap: {
         Secp256K1(secp256k1::Error),
+        Io { format: std::io::Error },
+        Json { format: serde_json::Error },
     },
     stack: {
         InvalidRecoverId(recid: u8),
         FromCompact(sig: [u8; 65]),
         RecoverSig(sig: [u8; 65]),
+        LoadKeystore(path: String),
+        SaveKeystore(path: String),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{hex, sol, sol_types::eip712_domain};
+
+    use super::*;
+
+    fn keypair(sk: &[u8]) -> Keypair {
+        Keypair::from_parts(None, SecretKey::from_slice(sk).unwrap())
+    }
+
+    #[test]
+    fn test_eip191_vector() {
+        let kp = keypair(&hex!(
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+        ));
+        let sig = kp.sign_message("Some data");
+        assert_eq!(
+            sig,
+            hex!("b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c")
+        );
+        assert_eq!(
+            Keypair::recover_message("Some data", sig).unwrap(),
+            kp.address()
+        );
+    }
+
+    sol! {
+        struct Person {
+            string name;
+            address wallet;
+        }
+
+        struct Mail {
+            Person from;
+            Person to;
+            string contents;
+        }
+    }
+
+    // the Mail example of the EIP-712 specification
+    #[test]
+    fn test_eip712_mail_vector() {
+        let domain = eip712_domain! {
+            name: "Ether Mail",
+            version: "1",
+            chain_id: 1,
+            verifying_contract: Address::repeat_byte(0xcc),
+        };
+        let mail = Mail {
+            from: Person {
+                name: "Cow".into(),
+                wallet: "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+                    .parse()
+                    .unwrap(),
+            },
+            to: Person {
+                name: "Bob".into(),
+                wallet: "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"
+                    .parse()
+                    .unwrap(),
+            },
+            contents: "Hello, Bob!".into(),
+        };
+        assert_eq!(
+            mail.eip712_signing_hash(&domain).0,
+            hex!("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+
+        let kp = keypair(&keccak256("cow").0);
+        assert_eq!(kp.address(), mail.from.wallet);
+        let sig = kp.sign_typed_data(&mail, &domain);
+        assert_eq!(
+            sig,
+            hex!("4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c")
+        );
+        assert_eq!(
+            Keypair::recover_typed_data(&mail, &domain, sig).unwrap(),
+            kp.address()
+        );
+
+        // v as 0/1 recovers the same signer
+        let mut raw = sig;
+        raw[64] -= 27;
+        assert_eq!(
+            Keypair::recover_typed_data(&mail, &domain, raw).unwrap(),
+            kp.address()
+        );
     }
 }
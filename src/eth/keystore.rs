@@ -0,0 +1,511 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use alloy::{
+    hex,
+    primitives::{keccak256, Address, U256},
+};
+use secp256k1::rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{Keypair, KeypairError, SecretKey};
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+const KEYSTORE_VERSION: u32 = 3;
+const KEYSTORE_CIPHER: &str = "aes-128-ctr";
+const KEYSTORE_DKLEN: u64 = 32;
+const KEYSTORE_IV_LEN: usize = 16;
+
+// the parameters eth-keystore writes, N = 2^13
+const SCRYPT_LOG_N: u8 = 13;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+// the kdf runs with whatever the file asks for, cap the cost so a crafted
+// file can't pin the cpu or exhaust memory (128 * n * r bytes).
+// geth's standard parameters are n=2^18, r=8, p=1.
+const MAX_SCRYPT_N: u64 = 1 << 20;
+const MAX_SCRYPT_R: u64 = 8;
+const MAX_SCRYPT_P: u64 = 16;
+const MAX_PBKDF2_C: u64 = 1 << 22;
+
+// Wraps the encrypted keystore before it reaches the disk and unwraps it
+// after reading, e.g. with an enclave sealing key so the file can only be
+// opened on the same platform.
+pub trait KeySealer: Send + Sync {
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, KeypairError>;
+    fn unseal(&self, data: &[u8]) -> Result<Vec<u8>, KeypairError>;
+}
+
+// the plain Web3 Secret Storage file, readable by geth and friends
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoSealer;
+
+impl KeySealer for NoSealer {
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, KeypairError> {
+        Ok(data.to_vec())
+    }
+
+    fn unseal(&self, data: &[u8]) -> Result<Vec<u8>, KeypairError> {
+        Ok(data.to_vec())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    #[serde(default)]
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    #[serde(alias = "Crypto")]
+    crypto: KeystoreCrypto,
+    // not part of the standard, other tools ignore it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    instance_id: Option<U256>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreCrypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: KdfParams,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum KdfParams {
+    Pbkdf2 {
+        c: u64,
+        dklen: u64,
+        prf: String,
+        salt: String,
+    },
+    Scrypt {
+        dklen: u64,
+        n: u64,
+        r: u64,
+        p: u64,
+        salt: String,
+    },
+}
+
+impl KdfParams {
+    fn derive(&self, password: &[u8]) -> Result<Vec<u8>, KeypairError> {
+        let mut key = vec![0_u8; KEYSTORE_DKLEN as usize];
+        match self {
+            Self::Pbkdf2 { c, salt, .. } => {
+                pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+                    password,
+                    &decode_hex(salt)?,
+                    *c as u32,
+                    &mut key,
+                );
+            }
+            Self::Scrypt { n, r, p, salt, .. } => {
+                let params = scrypt::Params::new(n.trailing_zeros() as u8, *r as u32, *p as u32)
+                    .map_err(|err| KeypairError::InvalidKeystore(format!("scrypt: {}", err)))?;
+                scrypt::scrypt(password, &decode_hex(salt)?, &params, &mut key)
+                    .map_err(|err| KeypairError::InvalidKeystore(format!("scrypt: {}", err)))?;
+            }
+        }
+        Ok(key)
+    }
+}
+
+impl KeystoreFile {
+    fn check(&self) -> Result<(), KeypairError> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(KeypairError::UnsupportedKeystore(format!(
+                "version {}",
+                self.version
+            )));
+        }
+        let crypto = &self.crypto;
+        if crypto.cipher != KEYSTORE_CIPHER {
+            return Err(KeypairError::UnsupportedKeystore(format!(
+                "cipher {}",
+                crypto.cipher
+            )));
+        }
+        let iv = decode_hex(&crypto.cipherparams.iv)?;
+        if iv.len() != KEYSTORE_IV_LEN {
+            return Err(KeypairError::InvalidKeystore(format!(
+                "iv length {}",
+                iv.len()
+            )));
+        }
+
+        let dklen = match &crypto.kdfparams {
+            KdfParams::Pbkdf2 { c, dklen, prf, .. } => {
+                if crypto.kdf != "pbkdf2" {
+                    return Err(KeypairError::InvalidKeystore(format!(
+                        "{} with pbkdf2 params",
+                        crypto.kdf
+                    )));
+                }
+                if prf != "hmac-sha256" {
+                    return Err(KeypairError::UnsupportedKeystore(format!(
+                        "pbkdf2 prf {}",
+                        prf
+                    )));
+                }
+                if *c == 0 || *c > MAX_PBKDF2_C {
+                    return Err(KeypairError::InvalidKeystore(format!("pbkdf2 c={}", c)));
+                }
+                *dklen
+            }
+            KdfParams::Scrypt { dklen, n, r, p, .. } => {
+                if crypto.kdf != "scrypt" {
+                    return Err(KeypairError::InvalidKeystore(format!(
+                        "{} with scrypt params",
+                        crypto.kdf
+                    )));
+                }
+                if *n < 2 || !n.is_power_of_two() || *n > MAX_SCRYPT_N {
+                    return Err(KeypairError::InvalidKeystore(format!("scrypt n={}", n)));
+                }
+                if *r == 0 || *r > MAX_SCRYPT_R || *p == 0 || *p > MAX_SCRYPT_P {
+                    return Err(KeypairError::InvalidKeystore(format!(
+                        "scrypt r={} p={}",
+                        r, p
+                    )));
+                }
+                *dklen
+            }
+        };
+        if dklen != KEYSTORE_DKLEN {
+            return Err(KeypairError::InvalidKeystore(format!("dklen {}", dklen)));
+        }
+        Ok(())
+    }
+}
+
+impl Keypair {
+    // writes a Web3 Secret Storage v3 file with the address and
+    // `instance_id` added next to the standard fields. the key is encrypted
+    // and sealed in memory, only the sealed bytes reach the disk: a private
+    // temp file renamed over, so a crash never leaves a truncated key behind.
+    pub fn save_keystore(
+        &self,
+        path: impl AsRef<Path>,
+        password: &str,
+        sealer: &dyn KeySealer,
+    ) -> Result<(), KeypairError> {
+        let path = path.as_ref();
+        let path_str = path.display().to_string();
+        let tmp = sibling_path(path, "tmp");
+        let result = self.write_keystore(path, &tmp, password, sealer);
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        result.map_err(KeypairError::SaveKeystore(&path_str))
+    }
+
+    pub fn load_keystore(
+        path: impl AsRef<Path>,
+        password: &str,
+        sealer: &dyn KeySealer,
+    ) -> Result<Self, KeypairError> {
+        let path = path.as_ref();
+        let path_str = path.display().to_string();
+        Self::read_keystore(path, password, sealer).map_err(KeypairError::LoadKeystore(&path_str))
+    }
+
+    fn write_keystore(
+        &self,
+        path: &Path,
+        tmp: &Path,
+        password: &str,
+        sealer: &dyn KeySealer,
+    ) -> Result<(), KeypairError> {
+        let data = sealer.seal(&self.encrypt_keystore(password)?)?;
+        // left behind by a crash between the write and the rename
+        if tmp.exists() {
+            std::fs::remove_file(tmp)?;
+        }
+        write_private(tmp, &data)?;
+        std::fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn encrypt_keystore(&self, password: &str) -> Result<Vec<u8>, KeypairError> {
+        let mut rng = thread_rng();
+        let mut salt = [0_u8; 32];
+        let mut iv = [0_u8; KEYSTORE_IV_LEN];
+        let mut id = [0_u8; 16];
+        rng.fill(&mut salt);
+        rng.fill(&mut iv);
+        rng.fill(&mut id);
+
+        let kdfparams = KdfParams::Scrypt {
+            dklen: KEYSTORE_DKLEN,
+            n: 1 << SCRYPT_LOG_N,
+            r: SCRYPT_R.into(),
+            p: SCRYPT_P.into(),
+            salt: hex::encode(salt),
+        };
+        let key = kdfparams.derive(password.as_bytes())?;
+        let mut ciphertext = self.secret_key().secret_bytes().to_vec();
+        Aes128Ctr::new(key[..16].into(), (&iv).into()).apply_keystream(&mut ciphertext);
+
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            id: uuid_v4(id),
+            address: Some(hex::encode(self.address())),
+            crypto: KeystoreCrypto {
+                cipher: KEYSTORE_CIPHER.into(),
+                cipherparams: CipherParams {
+                    iv: hex::encode(iv),
+                },
+                mac: hex::encode(keystore_mac(&key, &ciphertext)),
+                ciphertext: hex::encode(&ciphertext),
+                kdf: "scrypt".into(),
+                kdfparams,
+            },
+            instance_id: self.instance_id(),
+        };
+        Ok(serde_json::to_vec_pretty(&file)?)
+    }
+
+    fn read_keystore(
+        path: &Path,
+        password: &str,
+        sealer: &dyn KeySealer,
+    ) -> Result<Self, KeypairError> {
+        let data = sealer.unseal(&std::fs::read(path)?)?;
+        let file: KeystoreFile = serde_json::from_slice(&data)?;
+        file.check()?;
+
+        let crypto = &file.crypto;
+        let key = crypto.kdfparams.derive(password.as_bytes())?;
+        let mut secret = decode_hex(&crypto.ciphertext)?;
+        // a wrong password shows up here, before anything is decrypted
+        if !constant_time_eq(&keystore_mac(&key, &secret), &decode_hex(&crypto.mac)?) {
+            return Err(KeypairError::KeystoreMacMismatch);
+        }
+        let iv = decode_hex(&crypto.cipherparams.iv)?;
+        Aes128Ctr::new(key[..16].into(), iv.as_slice().into()).apply_keystream(&mut secret);
+
+        let kp = Self::from_parts(file.instance_id, SecretKey::from_slice(&secret)?);
+        if let Some(address) = file.address {
+            let expected: Address = address
+                .parse()
+                .map_err(|err| KeypairError::InvalidKeystore(format!("{}: {}", address, err)))?;
+            if expected != kp.address() {
+                return Err(KeypairError::AddressMismatch {
+                    expected,
+                    got: kp.address(),
+                });
+            }
+        }
+        Ok(kp)
+    }
+}
+
+fn keystore_mac(key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    let mut data = key[16..32].to_vec();
+    data.extend_from_slice(ciphertext);
+    keccak256(data).0
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn decode_hex(data: &str) -> Result<Vec<u8>, KeypairError> {
+    hex::decode(data).map_err(|err| KeypairError::InvalidKeystore(format!("{}: {}", data, err)))
+}
+
+fn uuid_v4(mut id: [u8; 16]) -> String {
+    id[6] = (id[6] & 0x0f) | 0x40;
+    id[8] = (id[8] & 0x3f) | 0x80;
+    let id = hex::encode(id);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &id[..8],
+        &id[8..12],
+        &id[12..16],
+        &id[16..20],
+        &id[20..]
+    )
+}
+
+fn sibling_path(path: &Path, ext: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(ext);
+    name.into()
+}
+
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    let mut file = opts.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::signers::local::PrivateKeySigner;
+
+    use super::*;
+
+    struct XorSealer(u8);
+
+    impl KeySealer for XorSealer {
+        fn seal(&self, data: &[u8]) -> Result<Vec<u8>, KeypairError> {
+            Ok(data.iter().map(|n| n ^ self.0).collect())
+        }
+
+        fn unseal(&self, data: &[u8]) -> Result<Vec<u8>, KeypairError> {
+            self.seal(data)
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("base-keystore-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    // the pbkdf2 test vector of the Web3 Secret Storage definition
+    const GETH_V3: &str = r#"{
+        "crypto": {
+            "cipher": "aes-128-ctr",
+            "cipherparams": {"iv": "6087dab2f9fdbbfaddc31a909735c1e6"},
+            "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf": "pbkdf2",
+            "kdfparams": {
+                "c": 262144,
+                "dklen": 32,
+                "prf": "hmac-sha256",
+                "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version": 3
+    }"#;
+    const GETH_V3_KEY: &str = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    #[test]
+    fn test_geth_v3_round_trip() {
+        let path = temp_path("geth");
+        std::fs::write(&path, GETH_V3).unwrap();
+        let kp = Keypair::load_keystore(&path, "testpassword", &NoSealer).unwrap();
+        assert_eq!(hex::encode(kp.secret_key().secret_bytes()), GETH_V3_KEY);
+
+        kp.save_keystore(&path, "testpassword", &NoSealer).unwrap();
+        let file: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(file["version"], 3);
+        assert_eq!(file["address"], hex::encode(kp.address()));
+        let loaded = Keypair::load_keystore(&path, "testpassword", &NoSealer).unwrap();
+        assert_eq!(loaded.secret_key(), kp.secret_key());
+        let err = Keypair::load_keystore(&path, "wrong", &NoSealer).unwrap_err();
+        assert!(
+            matches!(err.origin(), KeypairError::KeystoreMacMismatch),
+            "{:?}",
+            err
+        );
+
+        // what we write is still a standard keystore
+        let signer = PrivateKeySigner::decrypt_keystore(&path, "testpassword").unwrap();
+        assert_eq!(hex::encode(signer.to_bytes()), GETH_V3_KEY);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sealed_keystore_keeps_instance_id() {
+        let path = temp_path("sealed");
+        let kp = Keypair::from_parts(Some(U256::from(42)), *Keypair::new().secret_key());
+        kp.save_keystore(&path, "pass", &XorSealer(0x5a)).unwrap();
+        assert!(Keypair::load_keystore(&path, "pass", &NoSealer).is_err());
+
+        let loaded = Keypair::load_keystore(&path, "pass", &XorSealer(0x5a)).unwrap();
+        assert_eq!(loaded.instance_id(), Some(U256::from(42)));
+        assert_eq!(loaded.address(), kp.address());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_no_files_besides_keystore() {
+        let dir = std::env::temp_dir().join(format!("base-keystore-dir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("key.json");
+        // a crash between the write and the rename
+        std::fs::write(sibling_path(&path, "tmp"), "stale").unwrap();
+
+        let kp = Keypair::new();
+        for sealer in [&NoSealer as &dyn KeySealer, &XorSealer(0x5a)] {
+            kp.save_keystore(&path, "pass", sealer).unwrap();
+            let loaded = Keypair::load_keystore(&path, "pass", sealer).unwrap();
+            assert_eq!(loaded.secret_key(), kp.secret_key());
+            assert!(Keypair::load_keystore(&path, "wrong", sealer).is_err());
+
+            let files: Vec<_> = std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|n| n.unwrap().path())
+                .collect();
+            assert_eq!(files, vec![path.clone()]);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_kdf_bounds() {
+        let path = temp_path("bounds");
+        for (from, to) in [
+            ("\"c\": 262144", "\"c\": 4294967295"),
+            ("\"dklen\": 32", "\"dklen\": 16"),
+            ("6087dab2f9fdbbfaddc31a909735c1e6", "6087dab2"),
+        ] {
+            std::fs::write(&path, GETH_V3.replace(from, to)).unwrap();
+            let err = Keypair::load_keystore(&path, "testpassword", &NoSealer).unwrap_err();
+            assert!(
+                matches!(err.origin(), KeypairError::InvalidKeystore(_)),
+                "{}: {:?}",
+                to,
+                err
+            );
+        }
+        let scrypt = GETH_V3.replace(
+            r#""c": 262144,
+                "dklen": 32,
+                "prf": "hmac-sha256","#,
+            r#""dklen": 32, "n": 1073741824, "r": 8, "p": 1,"#,
+        );
+        std::fs::write(&path, scrypt.replace("pbkdf2", "scrypt")).unwrap();
+        let err = Keypair::load_keystore(&path, "testpassword", &NoSealer).unwrap_err();
+        assert!(
+            matches!(err.origin(), KeypairError::InvalidKeystore(_)),
+            "{:?}",
+            err
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
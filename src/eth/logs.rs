@@ -0,0 +1,222 @@
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::{Address, B256},
+    rpc::types::{Block, Filter, Log},
+    sol_types::SolEvent,
+    transports::RpcError,
+};
+
+use super::{Eth, EthError};
+
+#[derive(Clone, Debug)]
+pub struct LogQuery {
+    pub addresses: Vec<Address>,
+    pub from_block: u64,
+    pub to_block: u64,
+    // the initial span of each eth_getLogs, halved whenever the node refuses it
+    pub max_range: u64,
+    pub chunk_size: usize,
+}
+
+impl LogQuery {
+    pub fn new(addresses: Vec<Address>, from_block: u64, to_block: u64) -> Self {
+        Self {
+            addresses,
+            from_block,
+            to_block,
+            max_range: 2000,
+            chunk_size: 20,
+        }
+    }
+
+    fn ranges(&self, from: u64, to: u64, span: u64) -> Vec<(u64, u64)> {
+        let mut out = Vec::new();
+        let mut start = from;
+        while start <= to {
+            let end = to.min(start.saturating_add(span - 1));
+            out.push((start, end));
+            if end == u64::MAX {
+                break;
+            }
+            start = end + 1;
+        }
+        out
+    }
+}
+
+// the rejections nodes send for an oversized eth_getLogs. -32005 and plain
+// "limit" are left out on purpose, providers use them for rate limiting too.
+const TOO_MANY_RESULTS: &[&str] = &[
+    // geth, infura: "query returned more than 10000 results"
+    "query returned more than",
+    // erigon, nethermind
+    "block range too large",
+    // besu
+    "block range is too large",
+    // alchemy
+    "log response size exceeded",
+];
+
+#[derive(Clone, Debug)]
+pub struct DecodedEvent<E> {
+    pub address: Address,
+    pub block_number: Option<u64>,
+    pub block_hash: Option<B256>,
+    pub tx_hash: Option<B256>,
+    pub log_index: Option<u64>,
+    pub event: E,
+}
+
+impl EthError {
+    pub fn is_too_many_results(&self) -> bool {
+        match self.origin() {
+            Self::Rpc(RpcError::ErrorResp(payload)) => {
+                let msg = payload.message.to_lowercase();
+                TOO_MANY_RESULTS.iter().any(|n| msg.contains(n))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eth {
+    pub async fn get_events<E: SolEvent>(
+        &self,
+        query: &LogQuery,
+    ) -> Result<Vec<DecodedEvent<E>>, EthError> {
+        if query.from_block > query.to_block {
+            return Ok(Vec::new());
+        }
+
+        // only finalized ranges are stable enough to be served from the cache
+        let block: Result<Option<Block>, EthError> = self
+            .request_with(
+                "eth_getBlockByNumber",
+                (BlockNumberOrTag::Finalized, false),
+                false,
+            )
+            .await;
+        let finalized = match block {
+            Ok(block) => block.and_then(|n| n.header.number),
+            Err(err) => {
+                log::warn!(target: "eth", "fetch finalized block failed, skip cache: {:?}", err);
+                None
+            }
+        };
+
+        if let (Some(cache), Some(finalized)) = (self.cache(), finalized) {
+            cache.set_finalized(finalized);
+        }
+
+        let mut logs = Vec::new();
+        match finalized {
+            Some(finalized) if finalized >= query.from_block => {
+                let end = finalized.min(query.to_block);
+                logs.extend(
+                    self.fetch_logs::<E>(query, query.from_block, end, true)
+                        .await?,
+                );
+                if end < query.to_block {
+                    logs.extend(
+                        self.fetch_logs::<E>(query, end + 1, query.to_block, false)
+                            .await?,
+                    );
+                }
+            }
+            _ => {
+                let (from, to) = (query.from_block, query.to_block);
+                logs.extend(self.fetch_logs::<E>(query, from, to, false).await?);
+            }
+        }
+
+        let mut out = Vec::with_capacity(logs.len());
+        for log in logs {
+            let event = E::decode_log_data(log.data(), true).map_err(EthError::OnDecodeEvent(
+                &E::SIGNATURE,
+                &log.transaction_hash,
+                &log.log_index,
+            ))?;
+            out.push(DecodedEvent {
+                address: log.address(),
+                block_number: log.block_number,
+                block_hash: log.block_hash,
+                tx_hash: log.transaction_hash,
+                log_index: log.log_index,
+                event,
+            });
+        }
+        Ok(out)
+    }
+
+    async fn fetch_logs<E: SolEvent>(
+        &self,
+        query: &LogQuery,
+        from: u64,
+        to: u64,
+        cached: bool,
+    ) -> Result<Vec<Log>, EthError> {
+        let mut span = query.max_range.max(1);
+        loop {
+            let params: Vec<(Filter,)> = query
+                .ranges(from, to, span)
+                .into_iter()
+                .map(|(start, end)| {
+                    let filter = Filter::new()
+                        .address(query.addresses.clone())
+                        .event_signature(E::SIGNATURE_HASH)
+                        .from_block(start)
+                        .to_block(end);
+                    (filter,)
+                })
+                .collect();
+            let result: Result<Vec<Vec<Log>>, EthError> = self
+                .batch_request_chunks_with("eth_getLogs", &params, query.chunk_size, cached)
+                .await;
+            match result {
+                Ok(logs) => return Ok(logs.into_iter().flatten().collect()),
+                Err(err) if span > 1 && err.is_too_many_results() => {
+                    span /= 2;
+                    log::warn!(target: "eth", "eth_getLogs rejected, split range to {} blocks: {:?}", span, err);
+                }
+                Err(err) => return Err(EthError::GetLogs(&from, &to)(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::rpc::json_rpc::ErrorPayload;
+
+    use super::*;
+
+    fn rpc_error(code: i64, message: &'static str) -> EthError {
+        EthError::Rpc(RpcError::ErrorResp(ErrorPayload {
+            code,
+            message: message.to_owned(),
+            data: None,
+        }))
+    }
+
+    #[test]
+    fn test_is_too_many_results() {
+        for (code, msg) in [
+            (-32005, "query returned more than 10000 results"),
+            (-32000, "block range too large"),
+            (-32602, "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range"),
+        ] {
+            assert!(rpc_error(code, msg).is_too_many_results(), "{}", msg);
+        }
+        for (code, msg) in [
+            (-32005, "limit exceeded"),
+            (-32005, "daily request count exceeded, request rate limited"),
+            (
+                429,
+                "Your app has exceeded its compute units per second capacity",
+            ),
+            (-32000, "header not found"),
+        ] {
+            assert!(!rpc_error(code, msg).is_too_many_results(), "{}", msg);
+        }
+    }
+}
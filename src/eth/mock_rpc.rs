@@ -0,0 +1,116 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+pub struct MockResponse {
+    pub status: u16,
+    pub retry_after: Option<&'static str>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn result(req: &Value, result: impl Serialize) -> Self {
+        let body = serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": result});
+        Self {
+            status: 200,
+            retry_after: None,
+            body: body.to_string(),
+        }
+    }
+
+    // answers every call of a batch with `result(call)`
+    pub fn batch(req: &Value, result: impl Fn(&Value) -> Value) -> Self {
+        let body: Vec<Value> = req
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|call| serde_json::json!({"jsonrpc": "2.0", "id": call["id"], "result": result(call)}))
+            .collect();
+        Self {
+            status: 200,
+            retry_after: None,
+            body: Value::Array(body).to_string(),
+        }
+    }
+
+    pub fn status(status: u16, retry_after: Option<&'static str>) -> Self {
+        Self {
+            status,
+            retry_after,
+            body: "busy".to_owned(),
+        }
+    }
+}
+
+// minimal json-rpc over http server, `handler` answers the n-th request.
+// every connection is served on its own thread so concurrent requests
+// overlap. returns the url and the number of requests served so far.
+pub fn mock_rpc_server<F>(handler: F) -> (String, Arc<AtomicUsize>)
+where
+    F: Fn(usize, &Value) -> MockResponse + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let handler = Arc::new(handler);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let (counter, handler) = (counter.clone(), handler.clone());
+            std::thread::spawn(move || serve(stream, &counter, handler.as_ref()));
+        }
+    });
+    (url, hits)
+}
+
+fn serve<F>(mut stream: TcpStream, counter: &AtomicUsize, handler: &F)
+where
+    F: Fn(usize, &Value) -> MockResponse,
+{
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if key.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    // a client that gave up before sending anything
+    if content_length == 0 {
+        return;
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    let req: Value = serde_json::from_slice(&body).unwrap();
+
+    let n = counter.fetch_add(1, Ordering::SeqCst);
+    let resp = handler(n, &req);
+    let mut out = format!(
+        "HTTP/1.1 {} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        resp.status,
+        resp.body.len()
+    );
+    if let Some(retry_after) = resp.retry_after {
+        out += &format!("Retry-After: {}\r\n", retry_after);
+    }
+    out += "\r\n";
+    out += &resp.body;
+    // the client may have been aborted while the handler was busy
+    let _ = stream.write_all(out.as_bytes());
+}
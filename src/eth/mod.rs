@@ -11,3 +11,56 @@ pub use alloy::primitives;
 
 mod request_cache;
 pub use request_cache::*;
+
+mod retry;
+pub use retry::*;
+
+mod transport;
+pub use transport::*;
+
+mod endpoint;
+pub use endpoint::*;
+
+mod subscription;
+pub use subscription::*;
+
+mod tx_manager;
+pub use tx_manager::*;
+
+mod fee;
+pub use fee::*;
+
+mod multicall;
+pub use multicall::*;
+
+mod state_override;
+pub use state_override::*;
+
+mod logs;
+pub use logs::*;
+
+mod block_follower;
+pub use block_follower::*;
+
+mod fetch;
+
+mod cache_storage;
+pub use cache_storage::*;
+
+mod cache_encoding;
+pub use cache_encoding::*;
+
+mod signer;
+pub use signer::*;
+
+mod keystore;
+pub use keystore::*;
+
+mod key_rotation;
+pub use key_rotation::*;
+
+mod verify;
+pub use verify::*;
+
+#[cfg(test)]
+mod mock_rpc;
@@ -0,0 +1,268 @@
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use alloy::{
+    eips::BlockId,
+    primitives::{address, Address},
+    sol,
+    sol_types::SolCall,
+};
+
+use super::{Eth, EthError};
+
+pub const MULTICALL3_ADDRESS: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+
+// tags every Multicall so a handle can't be redeemed against another result
+static NEXT_MULTICALL_ID: AtomicU64 = AtomicU64::new(0);
+
+sol! {
+    #[derive(Debug)]
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct Call3Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (Call3Result[] memory returnData);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Multicall {
+    id: u64,
+    address: Address,
+    calls: Vec<IMulticall3::Call3>,
+    sigs: Vec<&'static str>,
+}
+
+impl Default for Multicall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multicall {
+    pub fn new() -> Self {
+        Self::with_address(MULTICALL3_ADDRESS)
+    }
+
+    pub fn with_address(address: Address) -> Self {
+        Self {
+            id: NEXT_MULTICALL_ID.fetch_add(1, Ordering::Relaxed),
+            address,
+            calls: Vec::new(),
+            sigs: Vec::new(),
+        }
+    }
+
+    pub fn add<T: SolCall>(
+        &mut self,
+        contract: Address,
+        call: &T,
+        allow_failure: bool,
+    ) -> MulticallHandle<T> {
+        self.calls.push(IMulticall3::Call3 {
+            target: contract,
+            allowFailure: allow_failure,
+            callData: call.abi_encode().into(),
+        });
+        self.sigs.push(T::SIGNATURE);
+        MulticallHandle {
+            multicall: self.id,
+            idx: self.calls.len() - 1,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct MulticallHandle<T> {
+    multicall: u64,
+    idx: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Clone for MulticallHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MulticallHandle<T> {}
+
+#[derive(Clone, Debug)]
+pub struct MulticallResult {
+    multicall: u64,
+    calls: Vec<(Address, &'static str)>,
+    results: Vec<IMulticall3::Call3Result>,
+}
+
+impl MulticallResult {
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn get<T: SolCall>(&self, handle: MulticallHandle<T>) -> Result<T::Return, EthError> {
+        // the handle must come from the Multicall this result was built from
+        let call = self.calls.get(handle.idx).zip(self.results.get(handle.idx));
+        let ((contract, sig), result) = match call {
+            Some(((_, sig), _)) if handle.multicall == self.multicall && *sig == T::SIGNATURE => {
+                call.unwrap()
+            }
+            _ => return Err(EthError::InvalidMulticallHandle),
+        };
+        if !result.success {
+            let err = EthError::MulticallFailed(result.returnData.clone());
+            return Err(EthError::OnCall(contract, sig)(err));
+        }
+        T::abi_decode_returns(&result.returnData, true).map_err(EthError::OnDecodeReturn(
+            contract,
+            sig,
+            &result.returnData,
+        ))
+    }
+}
+
+impl Eth {
+    pub async fn multicall(&self, multicall: &Multicall) -> Result<MulticallResult, EthError> {
+        self.multicall_at(multicall, BlockId::latest()).await
+    }
+
+    pub async fn multicall_at(
+        &self,
+        multicall: &Multicall,
+        block: BlockId,
+    ) -> Result<MulticallResult, EthError> {
+        let calls = multicall
+            .calls
+            .iter()
+            .zip(&multicall.sigs)
+            .map(|(call, sig)| (call.target, *sig))
+            .collect();
+        if multicall.is_empty() {
+            return Ok(MulticallResult {
+                multicall: multicall.id,
+                calls,
+                results: Vec::new(),
+            });
+        }
+        let aggregate = IMulticall3::aggregate3Call {
+            calls: multicall.calls.clone(),
+        };
+        let results = self
+            .call_at(multicall.address, &aggregate, block, None)
+            .await?
+            .returnData;
+        Ok(MulticallResult {
+            multicall: multicall.id,
+            calls,
+            results,
+        })
+    }
+
+    pub async fn multicall_same<T: SolCall>(
+        &self,
+        calls: &[(Address, T)],
+        allow_failure: bool,
+    ) -> Result<Vec<Result<T::Return, EthError>>, EthError> {
+        let mut multicall = Multicall::new();
+        let handles: Vec<_> = calls
+            .iter()
+            .map(|(contract, call)| multicall.add(*contract, call, allow_failure))
+            .collect();
+        let result = self.multicall(&multicall).await?;
+        Ok(handles.into_iter().map(|n| result.get(n)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::sol;
+
+    use super::*;
+
+    sol! {
+        function balanceOf(address owner) external view returns (uint256);
+        function decimals() external view returns (uint8);
+    }
+
+    fn result_of(multicall: &Multicall) -> MulticallResult {
+        MulticallResult {
+            multicall: multicall.id,
+            calls: multicall
+                .calls
+                .iter()
+                .zip(&multicall.sigs)
+                .map(|(call, sig)| (call.target, *sig))
+                .collect(),
+            results: multicall
+                .calls
+                .iter()
+                .map(|_| IMulticall3::Call3Result {
+                    success: true,
+                    returnData: vec![0; 32].into(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_handle_from_other_multicall() {
+        let token = Address::repeat_byte(1);
+        let mut a = Multicall::new();
+        let mut b = Multicall::new();
+        let handle = a.add(token, &decimalsCall {}, false);
+        let other_first = b.add(token, &decimalsCall {}, false);
+        let other_second = b.add(token, &decimalsCall {}, false);
+
+        let result = result_of(&a);
+        assert!(result.get(handle).is_ok());
+        // same type and index, but issued by another Multicall
+        assert!(matches!(
+            result.get(other_first),
+            Err(EthError::InvalidMulticallHandle)
+        ));
+        // out of range for `a`
+        assert!(matches!(
+            result.get(other_second),
+            Err(EthError::InvalidMulticallHandle)
+        ));
+    }
+
+    #[test]
+    fn test_handle_type_mismatch() {
+        let token = Address::repeat_byte(1);
+        let mut multicall = Multicall::new();
+        let handle = multicall.add(token, &decimalsCall {}, false);
+        let result = result_of(&multicall);
+        let forged = MulticallHandle::<balanceOfCall> {
+            multicall: handle.multicall,
+            idx: handle.idx,
+            _marker: PhantomData,
+        };
+        assert!(matches!(
+            result.get(forged),
+            Err(EthError::InvalidMulticallHandle)
+        ));
+    }
+}
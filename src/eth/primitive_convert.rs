@@ -1,9 +1,47 @@
-use alloy::primitives::{Bytes, U256, U64};
+use alloy::primitives::{Address, Bytes, B256, I256, U256, U64};
+use secp256k1::PublicKey;
+
+use crate::stack_error;
+
+use super::Keypair;
 
 pub trait PrimitivesConvert<T> {
     fn to(self) -> T;
 }
 
+// narrowing and parsing, fails instead of truncating
+pub trait PrimitivesTryConvert<T> {
+    fn try_to(self) -> Result<T, ConvertError>;
+}
+
+stack_error! {
+    #[derive(Clone, Debug)]
+    name: ConvertError,
+    stack_name: ConvertErrorStack,
+    error: {
+        Overflow { value: String, target: &'static str },
+        InvalidHex { value: String, target: &'static str },
+    },
+    wrap: {
+    },
+    stack: {
+    }
+}
+
+fn overflow<V: std::fmt::Display>(value: V, target: &'static str) -> ConvertError {
+    ConvertError::Overflow {
+        value: value.to_string(),
+        target,
+    }
+}
+
+fn invalid_hex(value: &str, target: &'static str) -> ConvertError {
+    ConvertError::InvalidHex {
+        value: value.to_owned(),
+        target,
+    }
+}
+
 impl<A, B> PrimitivesConvert<Option<B>> for Option<A>
 where
     A: PrimitivesConvert<B>,
@@ -13,6 +51,33 @@ where
     }
 }
 
+impl<A, B> PrimitivesConvert<Vec<B>> for Vec<A>
+where
+    A: PrimitivesConvert<B>,
+{
+    fn to(self) -> Vec<B> {
+        self.into_iter().map(A::to).collect()
+    }
+}
+
+impl<A, B> PrimitivesTryConvert<Option<B>> for Option<A>
+where
+    A: PrimitivesTryConvert<B>,
+{
+    fn try_to(self) -> Result<Option<B>, ConvertError> {
+        self.map(A::try_to).transpose()
+    }
+}
+
+impl<A, B> PrimitivesTryConvert<Vec<B>> for Vec<A>
+where
+    A: PrimitivesTryConvert<B>,
+{
+    fn try_to(self) -> Result<Vec<B>, ConvertError> {
+        self.into_iter().map(A::try_to).collect()
+    }
+}
+
 impl PrimitivesConvert<U256> for usize {
     fn to(self) -> U256 {
         U256::from_be_slice(&self.to_be_bytes())
@@ -42,3 +107,392 @@ impl PrimitivesConvert<Bytes> for Vec<u8> {
         Bytes::from(self)
     }
 }
+
+macro_rules! widen_unsigned {
+    ($($ty:ty),*) => {$(
+        impl PrimitivesConvert<U256> for $ty {
+            fn to(self) -> U256 {
+                U256::from(self)
+            }
+        }
+
+        impl PrimitivesConvert<U64> for $ty {
+            fn to(self) -> U64 {
+                U64::from(self)
+            }
+        }
+    )*};
+}
+
+widen_unsigned!(u8, u16, u32);
+
+macro_rules! narrow_unsigned {
+    ($($ty:ident),*) => {$(
+        impl PrimitivesTryConvert<$ty> for U256 {
+            fn try_to(self) -> Result<$ty, ConvertError> {
+                $ty::try_from(&self).map_err(|_| overflow(self, stringify!($ty)))
+            }
+        }
+
+        impl PrimitivesTryConvert<$ty> for U64 {
+            fn try_to(self) -> Result<$ty, ConvertError> {
+                $ty::try_from(&self).map_err(|_| overflow(self, stringify!($ty)))
+            }
+        }
+    )*};
+}
+
+narrow_unsigned!(u8, u16, u32, u64, u128, usize);
+
+macro_rules! signed {
+    ($($ty:ident),*) => {$(
+        impl PrimitivesConvert<I256> for $ty {
+            fn to(self) -> I256 {
+                I256::unchecked_from(self)
+            }
+        }
+
+        impl PrimitivesTryConvert<$ty> for I256 {
+            fn try_to(self) -> Result<$ty, ConvertError> {
+                $ty::try_from(self).map_err(|_| overflow(self, stringify!($ty)))
+            }
+        }
+    )*};
+}
+
+signed!(i8, i16, i32, i64, i128, isize);
+
+impl PrimitivesConvert<U256> for U64 {
+    fn to(self) -> U256 {
+        U256::from_limbs_slice(self.as_limbs())
+    }
+}
+
+impl PrimitivesConvert<u64> for U64 {
+    fn to(self) -> u64 {
+        self.as_limbs()[0]
+    }
+}
+
+impl PrimitivesTryConvert<U64> for U256 {
+    fn try_to(self) -> Result<U64, ConvertError> {
+        let n: u64 = self.try_to()?;
+        Ok(n.to())
+    }
+}
+
+impl PrimitivesTryConvert<I256> for U256 {
+    fn try_to(self) -> Result<I256, ConvertError> {
+        I256::try_from(self).map_err(|_| overflow(self, "I256"))
+    }
+}
+
+impl PrimitivesTryConvert<U256> for I256 {
+    fn try_to(self) -> Result<U256, ConvertError> {
+        U256::try_from(self).map_err(|_| overflow(self, "U256"))
+    }
+}
+
+impl PrimitivesConvert<U256> for B256 {
+    fn to(self) -> U256 {
+        U256::from_be_bytes(self.0)
+    }
+}
+
+impl PrimitivesConvert<B256> for U256 {
+    fn to(self) -> B256 {
+        B256::from(self.to_be_bytes::<32>())
+    }
+}
+
+// left padded, the layout of an address in a storage slot or log topic
+impl PrimitivesConvert<B256> for Address {
+    fn to(self) -> B256 {
+        self.into_word()
+    }
+}
+
+impl PrimitivesConvert<U256> for Address {
+    fn to(self) -> U256 {
+        U256::from_be_slice(self.as_slice())
+    }
+}
+
+impl PrimitivesTryConvert<Address> for B256 {
+    fn try_to(self) -> Result<Address, ConvertError> {
+        if self[..12].iter().any(|n| *n != 0) {
+            return Err(overflow(self, "Address"));
+        }
+        Ok(Address::from_word(self))
+    }
+}
+
+impl PrimitivesTryConvert<Address> for U256 {
+    fn try_to(self) -> Result<Address, ConvertError> {
+        PrimitivesConvert::<B256>::to(self).try_to()
+    }
+}
+
+impl PrimitivesConvert<Address> for PublicKey {
+    fn to(self) -> Address {
+        Keypair::public_key_to_address(&self)
+    }
+}
+
+impl PrimitivesConvert<Address> for &PublicKey {
+    fn to(self) -> Address {
+        Keypair::public_key_to_address(self)
+    }
+}
+
+impl PrimitivesConvert<Bytes> for &[u8] {
+    fn to(self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+}
+
+impl PrimitivesConvert<Vec<u8>> for Bytes {
+    fn to(self) -> Vec<u8> {
+        self.into()
+    }
+}
+
+// hex with or without the 0x prefix, quantities may have odd length
+impl PrimitivesTryConvert<U256> for &str {
+    fn try_to(self) -> Result<U256, ConvertError> {
+        // ruint reads no digits as zero, `u64::from_str_radix` rejects them
+        let digits = strip_hex(self);
+        if digits.is_empty() {
+            return Err(invalid_hex(self, "U256"));
+        }
+        U256::from_str_radix(digits, 16).map_err(|_| invalid_hex(self, "U256"))
+    }
+}
+
+impl PrimitivesTryConvert<U64> for &str {
+    fn try_to(self) -> Result<U64, ConvertError> {
+        let n: u64 = self.try_to()?;
+        Ok(n.to())
+    }
+}
+
+impl PrimitivesTryConvert<u64> for &str {
+    fn try_to(self) -> Result<u64, ConvertError> {
+        u64::from_str_radix(strip_hex(self), 16).map_err(|_| invalid_hex(self, "u64"))
+    }
+}
+
+impl PrimitivesTryConvert<B256> for &str {
+    fn try_to(self) -> Result<B256, ConvertError> {
+        self.parse().map_err(|_| invalid_hex(self, "B256"))
+    }
+}
+
+impl PrimitivesTryConvert<Address> for &str {
+    fn try_to(self) -> Result<Address, ConvertError> {
+        self.parse().map_err(|_| invalid_hex(self, "Address"))
+    }
+}
+
+impl PrimitivesTryConvert<Bytes> for &str {
+    fn try_to(self) -> Result<Bytes, ConvertError> {
+        self.parse().map_err(|_| invalid_hex(self, "Bytes"))
+    }
+}
+
+fn strip_hex(s: &str) -> &str {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+}
+
+// Implements the conversion for a struct field by field, every field goes
+// through `to`, a field prefixed with `?` through `try_to`:
+//
+//   primitives_convert!(RpcHeader => Header { number, hash, ?gas_used });
+//
+// with a `?` field the struct gets `PrimitivesTryConvert`, otherwise
+// `PrimitivesConvert`.
+#[macro_export]
+macro_rules! primitives_convert {
+    (@munch $v:ident, $from:ty, $to:ident, [$($mode:tt)*], [$($out:tt)*], ? $field:ident $(, $($rest:tt)*)?) => {
+        $crate::primitives_convert!(@munch $v, $from, $to, [try], [
+            $($out)* $field: $crate::eth::PrimitivesTryConvert::try_to($v.$field)?,
+        ], $($($rest)*)?);
+    };
+    (@munch $v:ident, $from:ty, $to:ident, [$($mode:tt)*], [$($out:tt)*], $field:ident $(, $($rest:tt)*)?) => {
+        $crate::primitives_convert!(@munch $v, $from, $to, [$($mode)*], [
+            $($out)* $field: $crate::eth::PrimitivesConvert::to($v.$field),
+        ], $($($rest)*)?);
+    };
+    (@munch $v:ident, $from:ty, $to:ident, [], [$($out:tt)*], ) => {
+        impl $crate::eth::PrimitivesConvert<$to> for $from {
+            fn to(self) -> $to {
+                let $v = self;
+                $to { $($out)* }
+            }
+        }
+    };
+    (@munch $v:ident, $from:ty, $to:ident, [try], [$($out:tt)*], ) => {
+        impl $crate::eth::PrimitivesTryConvert<$to> for $from {
+            fn try_to(self) -> Result<$to, $crate::eth::ConvertError> {
+                let $v = self;
+                Ok($to { $($out)* })
+            }
+        }
+    };
+    ($from:ty => $to:ident { $($fields:tt)* }) => {
+        $crate::primitives_convert!(@munch value, $from, $to, [], [], $($fields)*);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RpcHeader {
+        number: U64,
+        hash: B256,
+        gas_used: U256,
+    }
+
+    struct Header {
+        number: u64,
+        hash: U256,
+        gas_used: u64,
+    }
+
+    struct RpcBlock {
+        number: U64,
+        miner: Address,
+    }
+
+    struct Block {
+        number: U256,
+        miner: B256,
+    }
+
+    primitives_convert!(RpcHeader => Header { number, hash, ?gas_used });
+    primitives_convert!(RpcBlock => Block { number, miner });
+
+    fn to<T>(value: impl PrimitivesConvert<T>) -> T {
+        value.to()
+    }
+
+    fn try_to<T>(value: impl PrimitivesTryConvert<T>) -> Result<T, ConvertError> {
+        value.try_to()
+    }
+
+    fn is_overflow<T>(result: Result<T, ConvertError>) -> bool {
+        matches!(result, Err(ConvertError::Overflow { .. }))
+    }
+
+    fn is_invalid_hex<T>(result: Result<T, ConvertError>) -> bool {
+        matches!(result, Err(ConvertError::InvalidHex { .. }))
+    }
+
+    #[test]
+    fn test_widen() {
+        assert_eq!(to::<U256>(u64::MAX), U256::from(u64::MAX));
+        assert_eq!(to::<U256>(u128::MAX), U256::from(u128::MAX));
+        assert_eq!(to::<U256>(usize::MAX), U256::from(usize::MAX));
+        assert_eq!(to::<U64>(u8::MAX), U64::from(255));
+        assert_eq!(to::<U256>(U64::MAX), U256::from(u64::MAX));
+        assert_eq!(to::<u64>(U64::from(7)), 7);
+        assert_eq!(
+            to::<Vec<U256>>(vec![1_u32, 2]),
+            vec![U256::from(1), U256::from(2)]
+        );
+
+        let address = Address::repeat_byte(0x11);
+        let word = to::<B256>(address);
+        let n = to::<U256>(address);
+        assert_eq!(to::<U256>(word), n);
+        assert_eq!(to::<B256>(n), word);
+        assert_eq!(try_to::<Address>(word).unwrap(), address);
+        assert_eq!(try_to::<Address>(n).unwrap(), address);
+    }
+
+    #[test]
+    fn test_narrow() {
+        assert_eq!(try_to::<u8>(U256::from(255)).unwrap(), 255);
+        assert!(is_overflow(try_to::<u8>(U256::from(256))));
+        assert_eq!(try_to::<u64>(U256::from(u64::MAX)).unwrap(), u64::MAX);
+        assert!(is_overflow(try_to::<u64>(
+            U256::from(u64::MAX) + U256::from(1)
+        )));
+        assert!(is_overflow(try_to::<U64>(U256::MAX)));
+        assert!(is_overflow(try_to::<u32>(U64::MAX)));
+        assert!(is_overflow(try_to::<Address>(B256::repeat_byte(1))));
+
+        let values = vec![U256::from(1), U256::from(2)];
+        assert_eq!(try_to::<Vec<u16>>(values).unwrap(), vec![1, 2]);
+        assert!(is_overflow(try_to::<Vec<u16>>(vec![
+            U256::from(1),
+            U256::MAX
+        ])));
+        assert_eq!(try_to::<Option<u8>>(None::<U256>).unwrap(), None);
+    }
+
+    #[test]
+    fn test_signed() {
+        let five = I256::try_from(5).unwrap();
+        assert_eq!(to::<I256>(-1_i64), I256::MINUS_ONE);
+        assert_eq!(try_to::<i8>(I256::MINUS_ONE).unwrap(), -1);
+        assert!(is_overflow(try_to::<i8>(I256::try_from(128).unwrap())));
+        assert!(is_overflow(try_to::<U256>(I256::MINUS_ONE)));
+        assert_eq!(try_to::<U256>(five).unwrap(), U256::from(5));
+        assert_eq!(try_to::<I256>(U256::from(5)).unwrap(), five);
+        assert!(is_overflow(try_to::<I256>(U256::MAX)));
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(try_to::<U256>("0x1f").unwrap(), U256::from(31));
+        assert_eq!(try_to::<U256>("0X1f").unwrap(), U256::from(31));
+        assert_eq!(try_to::<U256>("abc").unwrap(), U256::from(0xabc));
+        assert_eq!(try_to::<u64>("0x10").unwrap(), 16);
+        assert_eq!(try_to::<U64>("0x10").unwrap(), U64::from(16));
+        for value in ["", "0x", "0xzz"] {
+            assert!(is_invalid_hex(try_to::<U256>(value)), "{:?}", value);
+            assert!(is_invalid_hex(try_to::<u64>(value)), "{:?}", value);
+        }
+        assert!(is_invalid_hex(try_to::<u64>("0x10000000000000000")));
+
+        let address = Address::repeat_byte(0xab);
+        assert_eq!(
+            try_to::<Address>(address.to_string().as_str()).unwrap(),
+            address
+        );
+        assert!(is_invalid_hex(try_to::<Address>("0x01")));
+        assert_eq!(try_to::<Bytes>("0x0102").unwrap(), Bytes::from(vec![1, 2]));
+    }
+
+    #[test]
+    fn test_struct_convert() {
+        let header = try_to::<Header>(RpcHeader {
+            number: U64::from(1),
+            hash: B256::with_last_byte(2),
+            gas_used: U256::from(3),
+        })
+        .unwrap();
+        assert_eq!(header.number, 1);
+        assert_eq!(header.hash, U256::from(2));
+        assert_eq!(header.gas_used, 3);
+
+        let header = try_to::<Header>(RpcHeader {
+            number: U64::from(1),
+            hash: B256::ZERO,
+            gas_used: U256::MAX,
+        });
+        assert!(is_overflow(header));
+
+        let address = Address::repeat_byte(0x22);
+        let block = to::<Block>(RpcBlock {
+            number: U64::from(9),
+            miner: address,
+        });
+        assert_eq!(block.number, U256::from(9));
+        assert_eq!(block.miner, address.into_word());
+    }
+}
@@ -1,41 +1,418 @@
-use std::{future::Future, io, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use alloy::primitives::keccak256;
+use alloy::primitives::{keccak256, B256};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::value::RawValue;
 
+use crate::time::{now, Time};
+
+use super::{migrate_storage, CacheBackend, CacheEncoding, CacheStorage, FileStorage};
+
+crate::stack_error! {
+    #[derive(Debug)]
+    name: CacheError,
+    stack_name: CacheErrorStack,
+    error: {},
+    wrap: {
+        Io(io::Error),
+        Json(serde_json::Error),
+    },
+    stack: {
+        ReadEntry(key: B256),
+        WriteEntry(key: B256),
+    }
+}
+
+// `magic | keccak(payload)[..8] | payload`
+const ENTRY_MAGIC: u8 = 0xce;
+const ENTRY_CHECKSUM: usize = 8;
+const ENTRY_HEADER: usize = 1 + ENTRY_CHECKSUM;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CachePolicy {
+    Never,
+    Forever,
+    Ttl(Duration),
+    // only when the params reference a block hash, or a block number at or
+    // below the finalized head
+    PinnedOnly,
+}
+
+impl CachePolicy {
+    pub fn default_for(method: &str) -> Self {
+        match method {
+            "eth_chainId" | "net_version" => Self::Forever,
+            "eth_blockNumber"
+            | "eth_gasPrice"
+            | "eth_maxPriorityFeePerGas"
+            | "eth_blobBaseFee"
+            | "eth_feeHistory"
+            | "eth_estimateGas"
+            | "eth_syncing"
+            | "eth_getTransactionByHash"
+            | "eth_getTransactionReceipt"
+            | "eth_sendRawTransaction"
+            | "eth_sendTransaction"
+            | "eth_newFilter"
+            | "eth_newBlockFilter"
+            | "eth_getFilterChanges"
+            | "eth_getFilterLogs"
+            | "eth_uninstallFilter"
+            | "txpool_content"
+            | "txpool_status" => Self::Never,
+            _ => Self::PinnedOnly,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    // serve and store according to the cache policies
+    #[default]
+    ReadThrough,
+    // always ask the node and store every response, nulls included
+    Record,
+    // never ask the node, a miss is an error
+    Replay,
+    // bypass the cache
+    Passthrough,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheDecision {
+    Skip,
+    Store { ttl: Option<Duration> },
+    // the params carry a plain block number, cacheable once it is finalized
+    NeedFinalized { number: u64 },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub expired: u64,
+    pub evictions: u64,
+    pub corrupted: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    // only tracked when a size limit is set
+    pub entries: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheIndex {
+    tick: u64,
+    entries: HashMap<B256, (u64, u64)>,
+    order: BTreeMap<u64, B256>,
+    bytes: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, key: &B256) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.0);
+            entry.0 = tick;
+            self.order.insert(tick, *key);
+        }
+    }
+
+    fn insert(&mut self, key: &B256, size: u64) {
+        self.remove(key);
+        self.entries.insert(*key, (0, size));
+        self.bytes += size;
+        self.touch(key);
+    }
+
+    fn remove(&mut self, key: &B256) {
+        if let Some((tick, size)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.bytes -= size;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    stats: CacheStats,
+    index: Option<CacheIndex>,
+    finalized: Option<(u64, Time)>,
+}
+
 #[derive(Clone, Debug)]
 pub struct RequestCache {
-    base_path: PathBuf,
+    storage: Arc<dyn CacheStorage>,
+    policies: Arc<HashMap<Cow<'static, str>, CachePolicy>>,
+    max_bytes: Option<u64>,
+    finalized_refresh: Duration,
+    encoding: CacheEncoding,
+    state: Arc<Mutex<CacheState>>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct JsonCache {
     pub key: Box<serde_json::value::RawValue>,
     pub value: Box<serde_json::value::RawValue>,
+    // unix seconds, entries written before TTLs existed never expire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
 }
 
 impl RequestCache {
     pub fn new(base_path: PathBuf) -> Self {
-        let _ = std::fs::create_dir_all(&base_path);
-        Self { base_path }
+        Self::with_storage(Arc::new(FileStorage::new(base_path)))
+    }
+
+    pub fn open(backend: impl Into<CacheBackend>) -> io::Result<Self> {
+        Ok(Self::with_storage(backend.into().open()?))
+    }
+
+    pub fn with_storage(storage: Arc<dyn CacheStorage>) -> Self {
+        Self {
+            storage,
+            policies: Arc::new(HashMap::new()),
+            max_bytes: None,
+            finalized_refresh: Duration::from_secs(12),
+            encoding: CacheEncoding::default(),
+            state: Arc::new(Mutex::new(CacheState::default())),
+        }
+    }
+
+    pub fn with_policy(
+        mut self,
+        method: impl Into<Cow<'static, str>>,
+        policy: CachePolicy,
+    ) -> Self {
+        Arc::make_mut(&mut self.policies).insert(method.into(), policy);
+        self
+    }
+
+    // only affects new writes, entries in any encoding are readable
+    pub fn with_encoding(mut self, encoding: CacheEncoding) -> Self {
+        self.encoding = encoding;
+        self
     }
 
-    fn get_key(&self, key: &[u8]) -> PathBuf {
-        let key = keccak256(&key);
-        let fp = self.base_path.join(format!("{}.cache", key));
-        fp
+    pub fn with_finalized_refresh(mut self, interval: Duration) -> Self {
+        self.finalized_refresh = interval;
+        self
     }
 
-    pub fn add_cache(&self, key: &[u8], data: &[u8]) -> io::Result<()> {
-        std::fs::write(self.get_key(key), data)?;
+    // scans the storage once to build the LRU index, then evicts down to the limit
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> io::Result<Self> {
+        let mut index = CacheIndex::default();
+        for (key, size) in self.storage.entries()? {
+            index.insert(&key, size);
+        }
+        self.max_bytes = Some(max_bytes);
+        self.state.lock().unwrap().index = Some(index);
+        self.evict();
+        Ok(self)
+    }
+
+    pub fn storage(&self) -> &Arc<dyn CacheStorage> {
+        &self.storage
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        let mut stats = state.stats.clone();
+        if let Some(index) = &state.index {
+            stats.entries = index.entries.len() as u64;
+            stats.total_bytes = index.bytes;
+        }
+        stats
+    }
+
+    pub fn policy(&self, method: &str) -> CachePolicy {
+        match self.policies.get(method) {
+            Some(policy) => policy.clone(),
+            None => CachePolicy::default_for(method),
+        }
+    }
+
+    pub fn finalized(&self) -> Option<u64> {
+        self.state.lock().unwrap().finalized.map(|n| n.0)
+    }
+
+    pub fn set_finalized(&self, number: u64) {
+        let mut state = self.state.lock().unwrap();
+        let number = state.finalized.map_or(number, |n| n.0.max(number));
+        state.finalized = Some((number, Time::now()));
+    }
+
+    pub fn finalized_outdated(&self) -> bool {
+        match self.state.lock().unwrap().finalized {
+            Some((_, updated)) => {
+                Time::now().saturating_duration_since(updated) >= self.finalized_refresh
+            }
+            None => true,
+        }
+    }
+
+    pub fn decide<P: Serialize>(&self, method: &str, params: &P) -> CacheDecision {
+        match self.policy(method) {
+            CachePolicy::Never => CacheDecision::Skip,
+            CachePolicy::Forever => CacheDecision::Store { ttl: None },
+            CachePolicy::Ttl(ttl) => CacheDecision::Store { ttl: Some(ttl) },
+            CachePolicy::PinnedOnly => {
+                let params = match serde_json::to_value(params) {
+                    Ok(params) => params,
+                    Err(_) => return CacheDecision::Skip,
+                };
+                match pinned_block(&params) {
+                    Pinned::Yes => CacheDecision::Store { ttl: None },
+                    Pinned::No => CacheDecision::Skip,
+                    Pinned::Number(number) => match self.finalized() {
+                        Some(finalized) if number <= finalized => {
+                            CacheDecision::Store { ttl: None }
+                        }
+                        _ => CacheDecision::NeedFinalized { number },
+                    },
+                }
+            }
+        }
+    }
+
+    // copies every entry of `from`, e.g. a directory of legacy `.cache` files
+    pub fn migrate_from(&self, from: impl Into<CacheBackend>) -> io::Result<usize> {
+        let from = from.into().open()?;
+        let n = migrate_storage(from.as_ref(), self.storage.as_ref())?;
+        if self.max_bytes.is_some() {
+            let mut index = CacheIndex::default();
+            for (key, size) in self.storage.entries()? {
+                index.insert(&key, size);
+            }
+            self.state.lock().unwrap().index = Some(index);
+            self.evict();
+        }
+        Ok(n)
+    }
+
+    fn get_key(&self, key: &[u8]) -> B256 {
+        keccak256(key)
+    }
+
+    fn evict(&self) {
+        let Some(max_bytes) = self.max_bytes else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        let mut evicted = 0;
+        while let Some(index) = state.index.as_mut() {
+            if index.bytes <= max_bytes {
+                break;
+            }
+            let Some((_, oldest)) = index.order.pop_first() else {
+                break;
+            };
+            index.remove(&oldest);
+            if let Err(err) = self.storage.remove(&oldest) {
+                log::warn!(target: "cache", "evict {:?} failed: {:?}", oldest, err);
+            }
+            evicted += 1;
+        }
+        state.stats.evictions += evicted;
+    }
+
+    // wraps the payload with a checksum so a torn or bit-rotten entry is
+    // detected instead of deserialized
+    fn seal(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(ENTRY_HEADER + data.len());
+        out.push(ENTRY_MAGIC);
+        out.extend_from_slice(&keccak256(data)[..ENTRY_CHECKSUM]);
+        out.extend_from_slice(data);
+        out
+    }
+
+    // entries written before checksums existed are returned as is
+    fn unseal(data: &[u8]) -> Option<&[u8]> {
+        if data.first() != Some(&ENTRY_MAGIC) {
+            return Some(data);
+        }
+        let payload = data.get(ENTRY_HEADER..)?;
+        (keccak256(payload)[..ENTRY_CHECKSUM] == data[1..ENTRY_HEADER]).then_some(payload)
+    }
+
+    fn quarantine(&self, key: &B256) {
+        log::warn!(target: "cache", "quarantine corrupt cache entry: {:?}", key);
+        {
+            let mut state = self.state.lock().unwrap();
+            state.stats.corrupted += 1;
+            if let Some(index) = state.index.as_mut() {
+                index.remove(key);
+            }
+        }
+        if let Err(err) = self.storage.quarantine(key) {
+            log::warn!(target: "cache", "quarantine {:?} failed: {:?}", key, err);
+        }
+    }
+
+    fn read_entry(&self, key: &B256) -> Result<Option<Vec<u8>>, CacheError> {
+        let data = self.storage.get(key).map_err(CacheError::ReadEntry(key))?;
+        let mut state = self.state.lock().unwrap();
+        let Some(data) = data else {
+            state.stats.misses += 1;
+            return Ok(None);
+        };
+        state.stats.bytes_read += data.len() as u64;
+        if let Some(index) = state.index.as_mut() {
+            index.touch(key);
+        }
+        drop(state);
+
+        match Self::unseal(&data) {
+            Some(payload) => Ok(Some(payload.to_vec())),
+            None => {
+                self.quarantine(key);
+                self.state.lock().unwrap().stats.misses += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn add_cache(&self, key: &[u8], data: &[u8]) -> Result<(), CacheError> {
+        let key = self.get_key(key);
+        let data = Self::seal(data);
+        self.storage
+            .put(&key, &data)
+            .map_err(CacheError::WriteEntry(&key))?;
+        {
+            let mut state = self.state.lock().unwrap();
+            state.stats.bytes_written += data.len() as u64;
+            if let Some(index) = state.index.as_mut() {
+                index.insert(&key, data.len() as u64);
+            }
+        }
+        self.evict();
         Ok(())
     }
 
+    pub fn remove_cache(&self, key: &[u8]) -> io::Result<()> {
+        let key = self.get_key(key);
+        if let Some(index) = self.state.lock().unwrap().index.as_mut() {
+            index.remove(&key);
+        }
+        self.storage.remove(&key)
+    }
+
     pub fn get_cache(&self, key: &[u8]) -> Option<Vec<u8>> {
-        match std::fs::read(self.get_key(key)) {
-            Ok(data) => Some(data),
-            Err(_) => None,
+        match self.read_entry(&self.get_key(key)) {
+            Ok(data) => data,
+            Err(err) => {
+                log::warn!(target: "cache", "read cache failed: {:?}", err);
+                None
+            }
         }
     }
 
@@ -46,7 +423,55 @@ impl RequestCache {
         RawValue::from_string(serde_json::to_string(&key).unwrap()).unwrap()
     }
 
-    pub fn batch_json<V, I, K>(&self, params: I) -> Result<Vec<Option<V>>, serde_json::Error>
+    // an expired entry is removed and reported as a miss, an undecodable one
+    // is quarantined so the caller fetches it again
+    pub fn get_json<V>(&self, key: &RawValue) -> Result<Option<V>, CacheError>
+    where
+        V: DeserializeOwned,
+    {
+        let hash = self.get_key(key.get().as_bytes());
+        let Some(data) = self.read_entry(&hash)? else {
+            return Ok(None);
+        };
+        let val = match CacheEncoding::decode(&data) {
+            Ok(Some(val)) => val,
+            // written by a build with more encodings enabled, leave it alone
+            Ok(None) => {
+                log::warn!(target: "cache", "unsupported cache encoding: {:?}", hash);
+                self.state.lock().unwrap().stats.misses += 1;
+                return Ok(None);
+            }
+            Err(_) => {
+                self.quarantine(&hash);
+                self.state.lock().unwrap().stats.misses += 1;
+                return Ok(None);
+            }
+        };
+        if matches!(val.expires_at, Some(expires_at) if expires_at <= now().as_secs()) {
+            {
+                let mut state = self.state.lock().unwrap();
+                state.stats.expired += 1;
+                state.stats.misses += 1;
+            }
+            if let Err(err) = self.remove_cache(key.get().as_bytes()) {
+                log::warn!(target: "cache", "remove expired cache failed: {:?}", err);
+            }
+            return Ok(None);
+        }
+        match val.value.decode() {
+            Ok(value) => {
+                self.state.lock().unwrap().stats.hits += 1;
+                Ok(Some(value))
+            }
+            Err(_) => {
+                self.quarantine(&hash);
+                self.state.lock().unwrap().stats.misses += 1;
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn batch_json<V, I, K>(&self, params: I) -> Result<Vec<Option<V>>, CacheError>
     where
         V: DeserializeOwned,
         K: Serialize + std::fmt::Debug,
@@ -55,27 +480,59 @@ impl RequestCache {
         let mut out = Vec::new();
         for param in params {
             let key = self.json_key(param);
-            out.push(match self.get_cache(key.get().as_bytes()) {
-                Some(n) => {
-                    let val: JsonCache = serde_json::from_slice(&n)?;
-                    serde_json::from_str(val.value.get())?
-                }
-                None => None,
-            });
+            out.push(self.get_json(&key)?);
         }
         Ok(out)
     }
 
-    pub fn save_json<V>(&self, key: &RawValue, data: &V) -> io::Result<()>
+    pub fn save_json<V>(&self, key: &RawValue, data: &V) -> Result<(), CacheError>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        self.save_json_ttl(key, data, None)
+    }
+
+    pub fn save_json_ttl<V>(
+        &self,
+        key: &RawValue,
+        data: &V,
+        ttl: Option<Duration>,
+    ) -> Result<(), CacheError>
     where
         V: Serialize + DeserializeOwned,
     {
-        let data = RawValue::from_string(serde_json::to_string_pretty(&data).unwrap()).unwrap();
+        self.store_json(key, data, ttl, false)
+    }
+
+    // a fixture has to replay "not found" answers too, so nulls are kept
+    pub fn record_json<V>(&self, key: &RawValue, data: &V) -> Result<(), CacheError>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        self.store_json(key, data, None, true)
+    }
+
+    fn store_json<V>(
+        &self,
+        key: &RawValue,
+        data: &V,
+        ttl: Option<Duration>,
+        keep_null: bool,
+    ) -> Result<(), CacheError>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        let data = self.encoding.value_json(data)?;
+        // a null result means "not found yet", caching it would hide the value forever
+        if data.get() == "null" && !keep_null {
+            return Ok(());
+        }
         let cache = JsonCache {
             key: key.to_owned(),
             value: data,
+            expires_at: ttl.map(|ttl| (now() + ttl).as_secs()),
         };
-        let val = serde_json::to_vec_pretty(&cache).unwrap();
+        let val = self.encoding.encode(&cache)?;
 
         self.add_cache(key.get().as_bytes(), &val)?;
         Ok(())
@@ -85,17 +542,201 @@ impl RequestCache {
     where
         V: Serialize + DeserializeOwned,
         F: Future<Output = Result<V, E>>,
+        E: From<CacheError>,
     {
-        if let Some(value) = self.get_cache(key.get().as_bytes()) {
-            log::info!(target: "cache", "get from cache: {:?} -> {:?}", key, self.get_key(key.get().as_bytes()));
+        self.json_ttl(key, None, f).await
+    }
 
-            let val: JsonCache = serde_json::from_slice(&value).unwrap();
-            return Ok(serde_json::from_str(val.value.get()).unwrap());
+    pub async fn json_ttl<F, V, E>(
+        &self,
+        key: &RawValue,
+        ttl: Option<Duration>,
+        f: F,
+    ) -> Result<V, E>
+    where
+        V: Serialize + DeserializeOwned,
+        F: Future<Output = Result<V, E>>,
+        E: From<CacheError>,
+    {
+        if let Some(value) = self.get_json(key)? {
+            log::info!(target: "cache", "get from cache: {:?} -> {:?}", key, self.get_key(key.get().as_bytes()));
+            return Ok(value);
         }
 
         log::info!(target: "cache", "retrive from remote: {:?} -> {:?}", key, self.get_key(key.get().as_bytes()));
         let value = f.await?;
-        self.save_json(key, &value).unwrap();
+        if let Err(err) = self.save_json_ttl(key, &value, ttl) {
+            log::warn!(target: "cache", "save cache failed: {:?}", err);
+        }
         Ok(value)
     }
 }
+
+enum Pinned {
+    Yes,
+    No,
+    Number(u64),
+}
+
+// Scans the top level params from the end, the block parameter comes last in
+// every eth_* method that takes one, so storage slots or keys before it are
+// never mistaken for a block hash.
+fn pinned_block(params: &serde_json::Value) -> Pinned {
+    use serde_json::Value;
+
+    fn block_ref(s: &str) -> Option<Pinned> {
+        match s {
+            "latest" | "pending" | "safe" | "finalized" => Some(Pinned::No),
+            "earliest" => Some(Pinned::Yes),
+            s if s.len() == 66 && s.starts_with("0x") => Some(Pinned::Yes),
+            s => s
+                .strip_prefix("0x")
+                .and_then(|n| u64::from_str_radix(n, 16).ok())
+                .map(Pinned::Number),
+        }
+    }
+
+    let items = match params {
+        Value::Array(items) => items.as_slice(),
+        other => std::slice::from_ref(other),
+    };
+    for item in items.iter().rev() {
+        match item {
+            Value::String(s) => {
+                if let Some(pinned) = block_ref(s) {
+                    return pinned;
+                }
+            }
+            Value::Object(obj) => {
+                if let Some(Value::String(_)) = obj.get("blockHash") {
+                    return Pinned::Yes;
+                }
+                // a log filter is pinned by its upper bound, and a missing
+                // `toBlock` means latest
+                let is_filter = ["fromBlock", "toBlock", "address", "topics"]
+                    .iter()
+                    .any(|n| obj.contains_key(*n));
+                if is_filter && !obj.contains_key("toBlock") {
+                    return Pinned::No;
+                }
+                let mut found = None;
+                for key in ["blockNumber", "fromBlock", "toBlock"] {
+                    if let Some(Value::String(s)) = obj.get(key) {
+                        found = match (found, block_ref(s)) {
+                            (_, Some(Pinned::No)) => return Pinned::No,
+                            (Some(Pinned::Number(a)), Some(Pinned::Number(b))) => {
+                                Some(Pinned::Number(a.max(b)))
+                            }
+                            (Some(found), _) => Some(found),
+                            (None, other) => other,
+                        };
+                    }
+                }
+                if let Some(pinned) = found {
+                    return pinned;
+                }
+            }
+            _ => {}
+        }
+    }
+    Pinned::No
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pinned(params: serde_json::Value) -> Option<u64> {
+        match pinned_block(&params) {
+            Pinned::Yes => Some(u64::MAX),
+            Pinned::No => None,
+            Pinned::Number(n) => Some(n),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_entry_refetched() {
+        let dir = std::env::temp_dir().join(format!("base-corrupted-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = RequestCache::open(CacheBackend::Files(dir.clone())).unwrap();
+        let key = cache.json_key(("eth_chainId", ()));
+        let value: u64 = cache
+            .json(&key, async { Ok::<_, CacheError>(1) })
+            .await
+            .unwrap();
+        assert_eq!(value, 1);
+
+        let storage = FileStorage::new(dir.clone());
+        let hash = cache.get_key(key.get().as_bytes());
+        let mut data = std::fs::read(storage.path(&hash)).unwrap();
+        *data.last_mut().unwrap() ^= 0x01;
+        std::fs::write(storage.path(&hash), data).unwrap();
+
+        let value: u64 = cache
+            .json(&key, async { Ok::<_, CacheError>(2) })
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+        assert_eq!(cache.stats().corrupted, 1);
+        assert!(storage.quarantine_path(&hash).exists());
+
+        // the re-fetched value replaced the corrupted entry
+        let value: u64 = cache
+            .json(&key, async { Ok::<_, CacheError>(3) })
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_legacy_unsealed_entry() {
+        let dir = std::env::temp_dir().join(format!("base-legacy-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cache = RequestCache::open(CacheBackend::Files(dir.clone())).unwrap();
+        let key = cache.json_key(("eth_chainId", ()));
+        let hash = cache.get_key(key.get().as_bytes());
+
+        // what versions before checksums wrote: pretty json, no header
+        let entry = JsonCache {
+            key: key.clone(),
+            value: RawValue::from_string("\"0x1\"".to_owned()).unwrap(),
+            expires_at: None,
+        };
+        let storage = FileStorage::new(dir.clone());
+        std::fs::write(
+            storage.path(&hash),
+            serde_json::to_vec_pretty(&entry).unwrap(),
+        )
+        .unwrap();
+
+        let value: Option<String> = cache.get_json(&key).unwrap();
+        assert_eq!(value.as_deref(), Some("0x1"));
+        assert_eq!(cache.stats().corrupted, 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pinned_log_filter() {
+        let address = "0x0000000000000000000000000000000000000001";
+        assert_eq!(
+            pinned(json!([{"fromBlock": "0x1", "toBlock": "0x10", "address": address}])),
+            Some(0x10)
+        );
+        assert_eq!(
+            pinned(json!([{"fromBlock": "0x1", "address": address}])),
+            None
+        );
+        assert_eq!(pinned(json!([{"address": address, "topics": []}])), None);
+        assert_eq!(
+            pinned(json!([{"fromBlock": "0x1", "toBlock": "latest"}])),
+            None
+        );
+        assert_eq!(
+            pinned(json!([{"blockHash": format!("0x{}", "11".repeat(32))}])),
+            Some(u64::MAX)
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::{future::Future, time::Duration};
+
+use alloy::transports::{RpcError, TransportErrorKind};
+use secp256k1::rand::{thread_rng, Rng};
+
+use super::{EthError, HttpStatusError};
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: bool,
+    pub retry_http_status: Vec<u16>,
+    pub retry_rpc_codes: Vec<i64>,
+    pub retry_transport: bool,
+    pub retry_timeout: bool,
+    pub honor_backoff_hint: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self::exponential(1, Duration::ZERO)
+    }
+
+    pub fn exponential(max_attempts: usize, base_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            retry_http_status: vec![429, 500, 502, 503, 504],
+            // -32005: limit exceeded, 429: rate limited (alchemy, quicknode)
+            retry_rpc_codes: vec![-32005, 429],
+            retry_transport: true,
+            retry_timeout: true,
+            honor_backoff_hint: true,
+        }
+    }
+
+    pub fn is_retryable(&self, err: &EthError) -> bool {
+        match err.origin() {
+            EthError::Rpc(err) => self.is_retryable_rpc(err),
+            EthError::Timeout(_) => self.retry_timeout,
+            _ => false,
+        }
+    }
+
+    fn is_retryable_rpc(&self, err: &RpcError<TransportErrorKind>) -> bool {
+        match err {
+            RpcError::ErrorResp(payload) => self.retry_rpc_codes.contains(&payload.code),
+            RpcError::Transport(TransportErrorKind::HttpError(err)) => {
+                self.retry_http_status.contains(&err.status)
+            }
+            RpcError::Transport(TransportErrorKind::Custom(err)) => {
+                match err.downcast_ref::<HttpStatusError>() {
+                    Some(err) => self.retry_http_status.contains(&err.status),
+                    None => self.retry_transport,
+                }
+            }
+            RpcError::Transport(
+                TransportErrorKind::BackendGone | TransportErrorKind::MissingBatchResponse(_),
+            ) => self.retry_transport,
+            _ => false,
+        }
+    }
+
+    // prefer the `Retry-After` header kept by `HttpTransport`, then fall back
+    // to a hint the node repeats in the body or error data.
+    pub fn backoff_hint(err: &EthError) -> Option<Duration> {
+        let data = match err.origin() {
+            EthError::Rpc(RpcError::ErrorResp(payload)) => payload.data.as_ref()?.get().to_owned(),
+            EthError::Rpc(RpcError::Transport(TransportErrorKind::Custom(err))) => {
+                let err = err.downcast_ref::<HttpStatusError>()?;
+                if err.retry_after.is_some() {
+                    return err.retry_after;
+                }
+                err.body.clone()
+            }
+            EthError::Rpc(RpcError::Transport(TransportErrorKind::HttpError(err))) => {
+                err.body.clone()
+            }
+            _ => return None,
+        };
+        let value: serde_json::Value = serde_json::from_str(&data).ok()?;
+        find_backoff_hint(&value, 4)
+    }
+
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let factor = 1_u32 << attempt.min(16);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter || delay.is_zero() {
+            return delay;
+        }
+        let half = delay / 2;
+        half + Duration::from_millis(thread_rng().gen_range(0..=half.as_millis() as u64))
+    }
+
+    pub async fn run<F, Fut, T>(&self, mut f: F) -> Result<T, EthError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, EthError>>,
+    {
+        let mut failures: Vec<(usize, String)> = Vec::new();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let err = match f().await {
+                Ok(n) => return Ok(n),
+                Err(err) => err,
+            };
+            if attempt >= self.max_attempts || !self.is_retryable(&err) {
+                return Err(failures.iter().fold(err, |err, (attempt, reason)| {
+                    EthError::RetryAttempt(attempt, reason.as_str())(err)
+                }));
+            }
+
+            let mut delay = self.delay(attempt - 1);
+            if self.honor_backoff_hint {
+                if let Some(hint) = Self::backoff_hint(&err) {
+                    delay = delay.max(hint);
+                }
+            }
+            log::warn!(target: "eth", "attempt {} failed, retry in {:?}: {:?}", attempt, delay, err);
+            failures.push((attempt, format!("{:?}", err.origin())));
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+fn find_backoff_hint(value: &serde_json::Value, depth: usize) -> Option<Duration> {
+    let obj = value.as_object()?;
+    for key in ["retry_after", "retryAfter", "backoff_seconds"] {
+        if let Some(secs) = obj.get(key).and_then(|n| n.as_f64()) {
+            return Some(Duration::from_secs_f64(secs.max(0.0)));
+        }
+    }
+    if depth == 0 {
+        return None;
+    }
+    obj.values().find_map(|n| find_backoff_hint(n, depth - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Instant,
+    };
+
+    use alloy::primitives::U64;
+
+    use super::*;
+    use crate::eth::{parse_retry_after, Eth, EthErrorStack};
+
+    // minimal json-rpc over http server, replies with `responses[n]` to the
+    // n-th request (the last one repeats) and counts the requests.
+    fn mock_server(responses: Vec<(u16, Option<&'static str>)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((key, value)) = line.split_once(':') {
+                        if key.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let req: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let (status, retry_after) = responses[n.min(responses.len() - 1)];
+                let body = match status {
+                    200 => serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": "0x10"})
+                        .to_string(),
+                    _ => "busy".to_owned(),
+                };
+                let mut resp = format!(
+                    "HTTP/1.1 {} MOCK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                    status,
+                    body.len()
+                );
+                if let Some(retry_after) = retry_after {
+                    resp += &format!("Retry-After: {}\r\n", retry_after);
+                }
+                resp += "\r\n";
+                resp += &body;
+                stream.write_all(resp.as_bytes()).unwrap();
+            }
+        });
+        (url, hits)
+    }
+
+    fn policy(max_attempts: usize) -> RetryPolicy {
+        RetryPolicy {
+            jitter: false,
+            ..RetryPolicy::exponential(max_attempts, Duration::from_millis(1))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_http_status() {
+        let (url, hits) = mock_server(vec![(429, Some("1")), (503, None), (200, None)]);
+        let mut eth = Eth::dial(&url, None).unwrap();
+        eth.with_retry(policy(3));
+
+        let now = Instant::now();
+        let number: U64 = eth.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(number, U64::from(0x10));
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        // the 429 asked for 1s, far above the 1ms exponential delay
+        assert!(now.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_exhausted() {
+        let (url, hits) = mock_server(vec![(503, None)]);
+        let mut eth = Eth::dial(&url, None).unwrap();
+        eth.with_retry(policy(3));
+
+        let err = eth
+            .request::<_, U64>("eth_blockNumber", ())
+            .await
+            .unwrap_err();
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let attempts: Vec<usize> = match &err {
+            EthError::Stack { stack, .. } => stack
+                .iter()
+                .filter_map(|n| match n {
+                    EthErrorStack::RetryAttempt { attempt, .. } => Some(*attempt),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        assert_eq!(attempts, vec![1, 2]);
+        match err.origin() {
+            EthError::Rpc(RpcError::Transport(TransportErrorKind::Custom(err))) => {
+                let err = err.downcast_ref::<HttpStatusError>().unwrap();
+                assert_eq!(err.status, 503);
+            }
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let (url, hits) = mock_server(vec![(400, None)]);
+        let mut eth = Eth::dial(&url, None).unwrap();
+        eth.with_retry(policy(3));
+
+        let result = eth.request::<_, U64>("eth_blockNumber", ()).await;
+        assert!(result.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_call_timeout_covers_retries() {
+        let (url, hits) = mock_server(vec![(503, Some("1"))]);
+        let mut eth = Eth::dial(&url, None).unwrap();
+        eth.with_retry(policy(5))
+            .with_call_timeout(Some(Duration::from_millis(300)));
+
+        let now = Instant::now();
+        let err = eth
+            .request::<_, U64>("eth_blockNumber", ())
+            .await
+            .unwrap_err();
+        assert!(matches!(err.origin(), EthError::Timeout(_)), "{:?}", err);
+        assert!(now.elapsed() < Duration::from_secs(1));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use alloy::{
+    consensus::SignableTransaction,
+    primitives::{Address, Bytes, ChainId, Signature, B256},
+    providers::network::{EthereumWallet, TxSigner},
+    rpc::client::{ClientBuilder, RpcClient},
+    signers::{
+        local::{coins_bip39::English, MnemonicBuilder, PrivateKeySigner},
+        Signer,
+    },
+    transports::BoxTransport,
+};
+use async_trait::async_trait;
+
+use super::{EthError, Keypair};
+
+#[derive(Clone, Debug)]
+pub enum EthSigner {
+    Local(PrivateKeySigner),
+    Keypair(Keypair),
+    Remote(RemoteSigner),
+}
+
+impl From<PrivateKeySigner> for EthSigner {
+    fn from(signer: PrivateKeySigner) -> Self {
+        Self::Local(signer)
+    }
+}
+
+impl From<Keypair> for EthSigner {
+    fn from(kp: Keypair) -> Self {
+        Self::Keypair(kp)
+    }
+}
+
+impl From<RemoteSigner> for EthSigner {
+    fn from(signer: RemoteSigner) -> Self {
+        Self::Remote(signer)
+    }
+}
+
+impl EthSigner {
+    pub fn private_key(hex: &str) -> Result<Self, EthError> {
+        Ok(Self::Local(hex.parse()?))
+    }
+
+    // an encrypted JSON keystore (Web3 Secret Storage) as written by geth or clef
+    pub fn keystore(path: impl AsRef<Path>, password: &str) -> Result<Self, EthError> {
+        Ok(Self::Local(PrivateKeySigner::decrypt_keystore(
+            path, password,
+        )?))
+    }
+
+    // `derivation_path` is a full BIP-32 path, e.g. "m/44'/60'/0'/0/0"
+    pub fn mnemonic(
+        phrase: &str,
+        derivation_path: &str,
+        password: Option<&str>,
+    ) -> Result<Self, EthError> {
+        let builder = MnemonicBuilder::<English>::default()
+            .phrase(phrase)
+            .derivation_path(derivation_path)?;
+        let builder = match password {
+            Some(password) => builder.password(password),
+            None => builder,
+        };
+        Ok(Self::Local(builder.build()?))
+    }
+
+    pub fn remote(url: &str, address: Address) -> Result<Self, EthError> {
+        Ok(Self::Remote(RemoteSigner::new(url, address)?))
+    }
+
+    pub fn address(&self) -> Address {
+        match self {
+            Self::Local(signer) => Signer::address(signer),
+            Self::Keypair(kp) => kp.address(),
+            Self::Remote(signer) => signer.address,
+        }
+    }
+
+    // the wallet is keyed by the current address, a rotated `Keypair` needs a
+    // new `Eth`
+    pub fn wallet(&self) -> EthereumWallet {
+        match self {
+            Self::Local(signer) => EthereumWallet::new(signer.clone()),
+            Self::Keypair(kp) => EthereumWallet::new(kp.clone()),
+            Self::Remote(signer) => EthereumWallet::new(signer.clone()),
+        }
+    }
+}
+
+// Speaks JSON-RPC over HTTP with a single method:
+//   signer_signHash([address, hash]) -> "0x" + r(32) + s(32) + v(1)
+// where v is 0/1 or 27/28. Anything that can answer that, e.g. a few lines
+// around a `Keypair`, can stand in for the real signer in tests.
+#[derive(Clone, Debug)]
+pub struct RemoteSigner {
+    client: RpcClient<BoxTransport>,
+    address: Address,
+    chain_id: Option<ChainId>,
+}
+
+impl RemoteSigner {
+    pub fn new(url: &str, address: Address) -> Result<Self, EthError> {
+        let client = ClientBuilder::default().http(url.try_into()?).boxed();
+        Ok(Self {
+            client,
+            address,
+            chain_id: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<Signature> {
+        let sig: Bytes = self
+            .client
+            .request("signer_signHash", (self.address, *hash))
+            .await
+            .map_err(alloy::signers::Error::other)?;
+        let sig = Signature::try_from(sig.as_ref()).map_err(alloy::signers::Error::other)?;
+        // never hand a signature of another key to the node
+        let signer = sig
+            .recover_address_from_prehash(hash)
+            .map_err(alloy::signers::Error::other)?;
+        if signer != self.address {
+            return Err(alloy::signers::Error::other(format!(
+                "remote signer returned a signature of {}, expected {}",
+                signer, self.address
+            )));
+        }
+        Ok(sig)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> Option<ChainId> {
+        self.chain_id
+    }
+
+    fn set_chain_id(&mut self, chain_id: Option<ChainId>) {
+        self.chain_id = chain_id;
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        sign_transaction(self, tx).await
+    }
+}
+
+#[async_trait]
+impl Signer for Keypair {
+    async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<Signature> {
+        let sig = Keypair::sign_digest_ecdsa(&self.secret_key(), hash.0);
+        Signature::try_from(&sig[..]).map_err(alloy::signers::Error::other)
+    }
+
+    fn address(&self) -> Address {
+        Keypair::address(self)
+    }
+
+    fn chain_id(&self) -> Option<ChainId> {
+        None
+    }
+
+    // the key is shared by every clone, transactions carry their own chain id
+    fn set_chain_id(&mut self, _: Option<ChainId>) {}
+}
+
+#[async_trait]
+impl TxSigner<Signature> for Keypair {
+    fn address(&self) -> Address {
+        Keypair::address(self)
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        sign_transaction(self, tx).await
+    }
+}
+
+async fn sign_transaction<S: Signer>(
+    signer: &S,
+    tx: &mut dyn SignableTransaction<Signature>,
+) -> alloy::signers::Result<Signature> {
+    if let Some(chain_id) = signer.chain_id() {
+        if !tx.set_chain_id_checked(chain_id) {
+            return Err(alloy::signers::Error::TransactionChainIdMismatch {
+                signer: chain_id,
+                tx: tx.chain_id().unwrap(),
+            });
+        }
+    }
+    let mut sig = signer.sign_hash(&tx.signature_hash()).await?;
+    if tx.use_eip155() {
+        if let Some(chain_id) = signer.chain_id().or_else(|| tx.chain_id()) {
+            sig = sig.with_chain_id(chain_id);
+        }
+    }
+    Ok(sig)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{hex, keccak256};
+
+    use super::*;
+    use crate::eth::mock_rpc::{mock_rpc_server, MockResponse};
+
+    // answers signer_signHash with `kp`, whatever address is asked for
+    fn stand_in_signer(kp: Keypair) -> String {
+        let (url, _) = mock_rpc_server(move |_, req| {
+            assert_eq!(req["method"], "signer_signHash");
+            let hash: B256 = serde_json::from_value(req["params"][1].clone()).unwrap();
+            let sig = Keypair::sign_digest_ecdsa(&kp.secret_key(), hash.0);
+            MockResponse::result(req, format!("0x{}", hex::encode(sig)))
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer() {
+        let kp = Keypair::new();
+        let signer = RemoteSigner::new(&stand_in_signer(kp.clone()), kp.address()).unwrap();
+        let hash = keccak256("remote");
+        let sig = signer.sign_hash(&hash).await.unwrap();
+        assert_eq!(
+            sig.recover_address_from_prehash(&hash).unwrap(),
+            kp.address()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_wrong_key() {
+        let expected = Keypair::new();
+        let signer =
+            RemoteSigner::new(&stand_in_signer(Keypair::new()), expected.address()).unwrap();
+        assert!(signer.sign_hash(&keccak256("remote")).await.is_err());
+    }
+}
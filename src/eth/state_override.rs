@@ -0,0 +1,31 @@
+use alloy::primitives::{Address, Bytes, B256, U256};
+
+pub use alloy::rpc::types::state::{AccountOverride, StateOverride};
+
+pub trait StateOverrideExt {
+    fn with_balance(&mut self, account: Address, balance: U256) -> &mut Self;
+    fn with_code(&mut self, account: Address, code: Bytes) -> &mut Self;
+    // patches a single slot and keeps the rest of the account storage
+    fn with_storage(&mut self, account: Address, slot: B256, value: B256) -> &mut Self;
+}
+
+impl StateOverrideExt for StateOverride {
+    fn with_balance(&mut self, account: Address, balance: U256) -> &mut Self {
+        self.entry(account).or_default().balance = Some(balance);
+        self
+    }
+
+    fn with_code(&mut self, account: Address, code: Bytes) -> &mut Self {
+        self.entry(account).or_default().code = Some(code);
+        self
+    }
+
+    fn with_storage(&mut self, account: Address, slot: B256, value: B256) -> &mut Self {
+        self.entry(account)
+            .or_default()
+            .state_diff
+            .get_or_insert_with(Default::default)
+            .insert(slot, value);
+        self
+    }
+}
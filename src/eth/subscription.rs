@@ -0,0 +1,147 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use alloy::{
+    primitives::B256,
+    pubsub::Subscription,
+    rpc::types::{Block, Filter, Log},
+    transports::TransportResult,
+};
+use async_trait::async_trait;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::trace::{Alive, AsyncIterator};
+
+use super::{Eth, EthError, EthProvider};
+
+type SubscribeFn<T> = Box<
+    dyn Fn(EthProvider) -> Pin<Box<dyn Future<Output = TransportResult<Subscription<T>>> + Send>>
+        + Send
+        + Sync,
+>;
+
+pub struct EthSubscription<T> {
+    eth: Eth,
+    alive: Alive,
+    name: &'static str,
+    subscribe: SubscribeFn<T>,
+    current: Option<Subscription<T>>,
+    resubscribe_delay: Duration,
+}
+
+impl<T> EthSubscription<T>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+{
+    fn new(eth: &Eth, alive: &Alive, name: &'static str, subscribe: SubscribeFn<T>) -> Self {
+        Self {
+            eth: eth.clone(),
+            alive: alive.clone(),
+            name,
+            subscribe,
+            current: None,
+            resubscribe_delay: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_resubscribe_delay(mut self, delay: Duration) -> Self {
+        self.resubscribe_delay = delay;
+        self
+    }
+
+    // takes the fields rather than `&self`: `&EthSubscription<T>` is only Send
+    // when `T: Sync`, and the future has to be Send for `AsyncIterator`
+    async fn try_subscribe(
+        eth: &Eth,
+        name: &'static str,
+        subscribe: &SubscribeFn<T>,
+    ) -> Result<Subscription<T>, EthError> {
+        let pool = eth.endpoints();
+        let mut last_err = None;
+        for idx in pool.candidates() {
+            let endpoint = &pool.endpoints()[idx];
+            match subscribe(endpoint.provider()).await {
+                Ok(sub) => return Ok(sub),
+                Err(err) => {
+                    pool.report_failure(idx);
+                    last_err = Some(EthError::OnSubscribe(&name, endpoint.url())(err));
+                }
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    // keeps retrying until a subscription is established or the alive is shut down
+    async fn resubscribe(&mut self) -> bool {
+        loop {
+            if !self.alive.is_alive() {
+                return false;
+            }
+            match Self::try_subscribe(&self.eth, self.name, &self.subscribe).await {
+                Ok(sub) => {
+                    self.current = Some(sub);
+                    return true;
+                }
+                Err(err) => {
+                    log::warn!(target: "eth", "subscribe {} failed, retry in {:?}: {:?}", self.name, self.resubscribe_delay, err);
+                    if !self.alive.sleep(self.resubscribe_delay).await {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T> AsyncIterator for EthSubscription<T>
+where
+    T: serde::de::DeserializeOwned + Send + 'static,
+{
+    type Item = T;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current.is_none() && !self.resubscribe().await {
+                return None;
+            }
+            let sub = self.current.as_mut().unwrap();
+            let result = tokio::select! {
+                _ = self.alive.wait_shutdown() => return None,
+                result = sub.recv() => result,
+            };
+            match result {
+                Ok(item) => return Some(item),
+                Err(RecvError::Lagged(n)) => {
+                    log::warn!(target: "eth", "subscription {} lagged, skipped {} items", self.name, n);
+                }
+                Err(RecvError::Closed) => {
+                    log::warn!(target: "eth", "subscription {} closed, resubscribing", self.name);
+                    self.current = None;
+                }
+            }
+        }
+    }
+}
+
+impl Eth {
+    pub fn subscribe_blocks(&self, alive: &Alive) -> EthSubscription<Block> {
+        let subscribe: SubscribeFn<Block> =
+            Box::new(|provider| Box::pin(async move { provider.subscribe_blocks().await }));
+        EthSubscription::new(self, alive, "newHeads", subscribe)
+    }
+
+    pub fn subscribe_logs(&self, alive: &Alive, filter: Filter) -> EthSubscription<Log> {
+        let subscribe: SubscribeFn<Log> = Box::new(move |provider| {
+            let filter = filter.clone();
+            Box::pin(async move { provider.subscribe_logs(&filter).await })
+        });
+        EthSubscription::new(self, alive, "logs", subscribe)
+    }
+
+    pub fn subscribe_pending_transactions(&self, alive: &Alive) -> EthSubscription<B256> {
+        let subscribe: SubscribeFn<B256> = Box::new(|provider| {
+            Box::pin(async move { provider.subscribe_pending_transactions().await })
+        });
+        EthSubscription::new(self, alive, "newPendingTransactions", subscribe)
+    }
+}
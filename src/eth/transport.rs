@@ -0,0 +1,114 @@
+use std::{task, time::Duration};
+
+use alloy::{
+    rpc::json_rpc::{RequestPacket, ResponsePacket},
+    transports::{
+        http::reqwest::{self, header::RETRY_AFTER, StatusCode},
+        utils::guess_local_url,
+        TransportError, TransportErrorKind, TransportFut,
+    },
+};
+use tower::Service;
+use url::Url;
+
+// `Http<reqwest::Client>` from alloy drops the response headers on a non-200
+// status, so `Retry-After` never reaches the retry policy. this transport is
+// the same json-rpc over http, but keeps the header in `HttpStatusError`.
+#[derive(Clone, Debug)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: Url,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpStatusError {
+    pub status: u16,
+    pub retry_after: Option<Duration>,
+    pub body: String,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP error {} with body: {}", self.status, self.body)?;
+        if let Some(retry_after) = self.retry_after {
+            write!(f, " (retry after {:?})", retry_after)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+impl HttpTransport {
+    pub fn new(url: Url) -> Self {
+        Self::with_client(reqwest::Client::new(), url)
+    }
+
+    pub fn with_client(client: reqwest::Client, url: Url) -> Self {
+        Self { client, url }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn is_local(&self) -> bool {
+        guess_local_url(self.url.as_str())
+    }
+
+    fn request(&self, req: RequestPacket) -> TransportFut<'static> {
+        let this = self.clone();
+        Box::pin(async move {
+            let resp = this
+                .client
+                .post(this.url)
+                .json(&req)
+                .send()
+                .await
+                .map_err(TransportErrorKind::custom)?;
+            let status = resp.status();
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|n| n.to_str().ok())
+                .and_then(parse_retry_after);
+            let body = resp.bytes().await.map_err(TransportErrorKind::custom)?;
+
+            if status != StatusCode::OK {
+                return Err(TransportErrorKind::custom(HttpStatusError {
+                    status: status.as_u16(),
+                    retry_after,
+                    body: String::from_utf8_lossy(&body).into_owned(),
+                }));
+            }
+
+            serde_json::from_slice(&body)
+                .map_err(|err| TransportError::deser_err(err, String::from_utf8_lossy(&body)))
+        })
+    }
+}
+
+impl Service<RequestPacket> for HttpTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> task::Poll<Result<(), Self::Error>> {
+        task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: RequestPacket) -> Self::Future {
+        self.request(req)
+    }
+}
+
+// Retry-After: <delay-seconds> | <http-date>
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.signed_duration_since(chrono::Utc::now());
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use alloy::{
+    eips::BlockId,
+    primitives::{Address, B256},
+    providers::network::TransactionBuilder,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    sol_types::SolCall,
+};
+use tokio::sync::Mutex;
+
+use crate::{time::Time, trace::Alive};
+
+use super::{Eth, EthError};
+
+#[derive(Clone, Debug)]
+pub struct TxManagerConfig {
+    pub poll_interval: Duration,
+    // a transaction still pending after this long is replaced with bumped fees,
+    // Duration::MAX never replaces it
+    pub stuck_timeout: Duration,
+    // a transaction unknown to the node for this long is reported as dropped
+    pub drop_timeout: Duration,
+    pub bump_percent: u128,
+    pub max_bumps: usize,
+}
+
+impl Default for TxManagerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(3),
+            stuck_timeout: Duration::from_secs(60),
+            drop_timeout: Duration::from_secs(180),
+            bump_percent: 15,
+            max_bumps: 5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PendingTx {
+    pub contract: Address,
+    pub sig: &'static str,
+    pub nonce: u64,
+    pub request: TransactionRequest,
+    // every hash broadcast for this nonce, the latest replacement last
+    pub hashes: Vec<B256>,
+    pub sent_at: Time,
+    pub bumps: usize,
+}
+
+impl PendingTx {
+    pub fn hash(&self) -> B256 {
+        *self.hashes.last().unwrap()
+    }
+}
+
+#[derive(Debug)]
+pub enum TxStatus {
+    Mined(TransactionReceipt),
+    // the error comes from replaying the call, use `EthError::revert_data` to decode it
+    Reverted(TransactionReceipt, Option<EthError>),
+    // the nonce was consumed by a transaction not tracked here
+    Replaced(u64),
+    Dropped(PendingTx),
+    // tracking stopped because the alive was shut down
+    Pending(PendingTx),
+}
+
+pub struct TxManager {
+    eth: Eth,
+    from: Address,
+    nonce: Mutex<Option<u64>>,
+    config: TxManagerConfig,
+}
+
+impl TxManager {
+    pub fn new(eth: Eth, from: Address, config: TxManagerConfig) -> Self {
+        Self {
+            eth,
+            from,
+            nonce: Mutex::new(None),
+            config,
+        }
+    }
+
+    pub fn from(&self) -> Address {
+        self.from
+    }
+
+    pub async fn reset_nonce(&self) {
+        *self.nonce.lock().await = None;
+    }
+
+    pub async fn send<T: SolCall>(
+        &self,
+        contract: Address,
+        call: &T,
+    ) -> Result<PendingTx, EthError> {
+        let request = TransactionRequest::default()
+            .with_call(call)
+            .to(contract)
+            .with_from(self.from);
+        let request = self
+            .eth
+            .prepare_transaction(request)
+            .await
+            .map_err(EthError::OnTransact(&contract, &T::SIGNATURE))?;
+
+        // hold the lock while broadcasting so nonces reach the node in order
+        let mut next_nonce = self.nonce.lock().await;
+        let nonce = match *next_nonce {
+            Some(nonce) => nonce,
+            None => self
+                .eth
+                .provider()
+                .get_transaction_count(self.from)
+                .block_id(BlockId::pending())
+                .await
+                .map_err(EthError::OnTransact(&contract, &T::SIGNATURE))?,
+        };
+        let request = request.with_nonce(nonce);
+        let hash = match self.broadcast(&request).await {
+            Ok(hash) => hash,
+            Err(err) => {
+                *next_nonce = None;
+                return Err(EthError::OnTransact(&contract, &T::SIGNATURE)(err));
+            }
+        };
+        *next_nonce = Some(nonce + 1);
+        log::info!(target: "eth", "sent {} to {:?}: nonce={}, hash={:?}", T::SIGNATURE, contract, nonce, hash);
+
+        Ok(PendingTx {
+            contract,
+            sig: T::SIGNATURE,
+            nonce,
+            request,
+            hashes: vec![hash],
+            sent_at: Time::now(),
+            bumps: 0,
+        })
+    }
+
+    pub async fn transact<T: SolCall>(
+        &self,
+        alive: &Alive,
+        contract: Address,
+        call: &T,
+    ) -> Result<TxStatus, EthError> {
+        let tx = self.send(contract, call).await?;
+        self.wait(alive, tx).await
+    }
+
+    pub async fn wait(&self, alive: &Alive, mut tx: PendingTx) -> Result<TxStatus, EthError> {
+        let mut last_seen = Time::now();
+        loop {
+            if let Some(status) = self.check_receipts(&tx).await? {
+                return Ok(status);
+            }
+
+            let confirmed = self
+                .eth
+                .provider()
+                .get_transaction_count(self.from)
+                .await
+                .map_err(EthError::OnTransact(&tx.contract, &tx.sig))?;
+            if confirmed > tx.nonce {
+                // our transaction may have been mined right after the receipts check
+                if let Some(status) = self.check_receipts(&tx).await? {
+                    return Ok(status);
+                }
+                return Ok(TxStatus::Replaced(tx.nonce));
+            }
+
+            let known = self
+                .eth
+                .provider()
+                .get_transaction_by_hash(tx.hash())
+                .await
+                .map_err(EthError::OnTransact(&tx.contract, &tx.sig))?
+                .is_some();
+            let now = Time::now();
+            if known {
+                last_seen = now;
+                if now >= tx.sent_at.saturating_add(self.config.stuck_timeout)
+                    && tx.bumps < self.config.max_bumps
+                {
+                    self.replace(&mut tx).await;
+                }
+            } else if now >= last_seen.saturating_add(self.config.drop_timeout) {
+                return Ok(TxStatus::Dropped(tx));
+            } else {
+                match self.broadcast(&tx.request).await {
+                    Ok(hash) => {
+                        if !tx.hashes.contains(&hash) {
+                            tx.hashes.push(hash);
+                        }
+                    }
+                    Err(err) => {
+                        log::warn!(target: "eth", "rebroadcast nonce={} hash={:?} failed: {:?}", tx.nonce, tx.hash(), err);
+                    }
+                }
+            }
+
+            if !alive.sleep(self.config.poll_interval).await {
+                return Ok(TxStatus::Pending(tx));
+            }
+        }
+    }
+
+    async fn check_receipts(&self, tx: &PendingTx) -> Result<Option<TxStatus>, EthError> {
+        let provider = self.eth.provider();
+        for hash in tx.hashes.iter().rev() {
+            let receipt = provider
+                .get_transaction_receipt(*hash)
+                .await
+                .map_err(EthError::OnTransact(&tx.contract, &tx.sig))?;
+            let Some(receipt) = receipt else {
+                continue;
+            };
+            if receipt.status() {
+                return Ok(Some(TxStatus::Mined(receipt)));
+            }
+            let err = self.replay(tx, receipt.block_number).await.err();
+            return Ok(Some(TxStatus::Reverted(receipt, err)));
+        }
+        Ok(None)
+    }
+
+    // the receipt carries no revert data, so run the call again on top of the
+    // parent block to recover it.
+    async fn replay(&self, tx: &PendingTx, block: Option<u64>) -> Result<(), EthError> {
+        let block = match block {
+            Some(n) => BlockId::number(n.saturating_sub(1)),
+            None => BlockId::latest(),
+        };
+        let mut request = tx.request.clone();
+        request.nonce = None;
+        self.eth
+            .provider()
+            .call(&request)
+            .block(block)
+            .await
+            .map_err(EthError::OnCall(&tx.contract, &tx.sig))?;
+        Ok(())
+    }
+
+    async fn replace(&self, tx: &mut PendingTx) {
+        let mut request = tx.request.clone();
+        if let Err(err) = self.fill_fees(tx.hash(), &mut request).await {
+            log::warn!(target: "eth", "fetch fees of {:?} failed: {:?}", tx.hash(), err);
+            return;
+        }
+        let bump = |n: u128| n + n * self.config.bump_percent / 100 + 1;
+        request.max_fee_per_gas = request.max_fee_per_gas.map(bump);
+        request.max_priority_fee_per_gas = request.max_priority_fee_per_gas.map(bump);
+        request.gas_price = request.gas_price.map(bump);
+
+        match self.broadcast(&request).await {
+            Ok(hash) => {
+                log::info!(target: "eth", "replaced nonce={} {:?} -> {:?}", tx.nonce, tx.hash(), hash);
+                tx.request = request;
+                tx.hashes.push(hash);
+                tx.sent_at = Time::now();
+                tx.bumps += 1;
+            }
+            Err(err) => {
+                log::warn!(target: "eth", "replace nonce={} failed: {:?}", tx.nonce, err);
+            }
+        }
+    }
+
+    // the first broadcast leaves gas and fees to the fillers, read them back
+    // from the node before bumping.
+    async fn fill_fees(
+        &self,
+        hash: B256,
+        request: &mut TransactionRequest,
+    ) -> Result<(), EthError> {
+        if request.max_fee_per_gas.is_some() || request.gas_price.is_some() {
+            return Ok(());
+        }
+        let sent = self.eth.provider().get_transaction_by_hash(hash).await?;
+        let Some(sent) = sent else {
+            return Ok(());
+        };
+        request.gas = Some(sent.gas);
+        match sent.max_fee_per_gas {
+            Some(max_fee) => {
+                request.max_fee_per_gas = Some(max_fee);
+                request.max_priority_fee_per_gas = sent.max_priority_fee_per_gas;
+            }
+            None => request.gas_price = sent.gas_price,
+        }
+        Ok(())
+    }
+
+    async fn broadcast(&self, request: &TransactionRequest) -> Result<B256, EthError> {
+        let provider = self.eth.provider();
+        let pending = provider.send_transaction(request.clone()).await?;
+        Ok(*pending.tx_hash())
+    }
+}
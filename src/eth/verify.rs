@@ -0,0 +1,142 @@
+use alloy::primitives::Address;
+use secp256k1::{
+    ecdsa::{RecoverableSignature, RecoveryId, Signature},
+    Message, SECP256K1,
+};
+
+use super::{Keypair, KeypairError};
+
+// Recovers and checks 65-byte r ++ s ++ v signatures. By default any chain id
+// is accepted and high-s signatures are rejected, since (r, n - s) is a second
+// valid signature for the same digest.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignatureVerifier {
+    chain_id: Option<u64>,
+    allow_high_s: bool,
+}
+
+impl SignatureVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // an EIP-155 encoded v must carry this chain id, 0/1 and 27/28 are still
+    // accepted
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    // e.g. for signatures of signers predating EIP-2
+    pub fn with_high_s(mut self, allow: bool) -> Self {
+        self.allow_high_s = allow;
+        self
+    }
+
+    pub fn recover(&self, digest: [u8; 32], sig: [u8; 65]) -> Result<Address, KeypairError> {
+        let mut rs = [0_u8; 64];
+        rs.copy_from_slice(&sig[..64]);
+        self.recover_parts(digest, rs, sig[64] as u64)
+    }
+
+    // for EIP-155 values that do not fit into the last byte
+    pub fn recover_parts(
+        &self,
+        digest: [u8; 32],
+        rs: [u8; 64],
+        v: u64,
+    ) -> Result<Address, KeypairError> {
+        let (recid, chain_id) = normalize_v(v)?;
+        if let (Some(expected), Some(got)) = (self.chain_id, chain_id) {
+            if expected != got {
+                return Err(KeypairError::ChainIdMismatch { expected, got });
+            }
+        }
+        if !self.allow_high_s && is_high_s(&rs)? {
+            return Err(KeypairError::HighS);
+        }
+
+        let mut sig = [0_u8; 65];
+        sig[..64].copy_from_slice(&rs);
+        sig[64] = recid;
+        let recid =
+            RecoveryId::from_i32(recid as _).map_err(KeypairError::InvalidRecoverId(&recid))?;
+        let rec_sig = RecoverableSignature::from_compact(&rs, recid)
+            .map_err(KeypairError::FromCompact(&sig))?;
+        let key = SECP256K1
+            .recover_ecdsa(&Message::from_digest(digest), &rec_sig)
+            .map_err(KeypairError::RecoverSig(&sig))?;
+        Ok(Keypair::public_key_to_address(&key))
+    }
+
+    pub fn verify(
+        &self,
+        digest: [u8; 32],
+        sig: [u8; 65],
+        expected: Address,
+    ) -> Result<(), KeypairError> {
+        let got = self.recover(digest, sig)?;
+        if got != expected {
+            return Err(KeypairError::AddressMismatch { expected, got });
+        }
+        Ok(())
+    }
+
+    // spreads the items over all cores, results keep the input order
+    pub fn recover_batch(
+        &self,
+        items: &[([u8; 32], [u8; 65])],
+    ) -> Vec<Result<Address, KeypairError>> {
+        par_map(items, |(digest, sig)| self.recover(*digest, *sig))
+    }
+
+    pub fn verify_batch(
+        &self,
+        items: &[([u8; 32], [u8; 65], Address)],
+    ) -> Vec<Result<(), KeypairError>> {
+        par_map(items, |(digest, sig, expected)| {
+            self.verify(*digest, *sig, *expected)
+        })
+    }
+}
+
+// the recovery id and, for EIP-155 encoded values, the chain id
+pub fn normalize_v(v: u64) -> Result<(u8, Option<u64>), KeypairError> {
+    match v {
+        0 | 1 => Ok((v as u8, None)),
+        27 | 28 => Ok((v as u8 - 27, None)),
+        35.. => Ok((((v - 35) % 2) as u8, Some((v - 35) / 2))),
+        _ => Err(KeypairError::InvalidV(v)),
+    }
+}
+
+fn is_high_s(rs: &[u8; 64]) -> Result<bool, KeypairError> {
+    let sig = Signature::from_compact(rs)?;
+    let mut low = sig;
+    low.normalize_s();
+    Ok(low != sig)
+}
+
+fn par_map<T, O, F>(items: &[T], f: F) -> Vec<O>
+where
+    T: Sync,
+    O: Send,
+    F: Fn(&T) -> O + Sync,
+{
+    let workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = items.len().div_ceil(workers).max(1);
+    if items.len() <= chunk_size {
+        return items.iter().map(f).collect();
+    }
+    let f = &f;
+    std::thread::scope(|s| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| s.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
@@ -53,3 +53,399 @@ pub fn ternary<T>(n: bool, a: T, b: T) -> T {
         b
     }
 }
+
+#[cfg(feature = "alloy")]
+pub use units::*;
+
+// U256 amounts as decimal strings, wei/gwei/ether or any ERC-20 decimals.
+// The arithmetic is done on the digits so nothing is lost for any `decimals`.
+#[cfg(feature = "alloy")]
+mod units {
+    use std::str::FromStr;
+
+    use alloy::primitives::U256;
+
+    use crate::stack_error;
+
+    stack_error! {
+        #[derive(Clone, Debug)]
+        name: UnitError,
+        stack_name: UnitErrorStack,
+        error: {
+            Invalid(String),
+            UnknownUnit(String),
+            // more fraction digits than the unit has decimals
+            Precision { value: String, decimals: u8 },
+            Overflow(String),
+        },
+        wrap: {
+        },
+        stack: {
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Unit {
+        Wei,
+        Gwei,
+        Ether,
+    }
+
+    impl Unit {
+        pub fn decimals(&self) -> u8 {
+            match self {
+                Self::Wei => 0,
+                Self::Gwei => 9,
+                Self::Ether => 18,
+            }
+        }
+    }
+
+    impl FromStr for Unit {
+        type Err = UnitError;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.to_ascii_lowercase().as_str() {
+                "wei" => Ok(Self::Wei),
+                "gwei" => Ok(Self::Gwei),
+                "ether" | "eth" => Ok(Self::Ether),
+                _ => Err(UnitError::UnknownUnit(s.to_owned())),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum Rounding {
+        // truncate
+        #[default]
+        Down,
+        Up,
+        HalfUp,
+        // banker's rounding
+        HalfEven,
+    }
+
+    impl Rounding {
+        fn round_up(&self, last_kept: u8, dropped: &[u8]) -> bool {
+            let nonzero = |digits: &[u8]| digits.iter().any(|n| *n != b'0');
+            match self {
+                Self::Down => false,
+                Self::Up => nonzero(dropped),
+                Self::HalfUp => dropped[0] >= b'5',
+                Self::HalfEven => match dropped[0] {
+                    b'5' => nonzero(&dropped[1..]) || (last_kept - b'0') % 2 == 1,
+                    n => n > b'5',
+                },
+            }
+        }
+    }
+
+    // thousands separators `parse_units` understands
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Separator {
+        Comma,
+        Underscore,
+    }
+
+    impl Separator {
+        const ALL: [char; 2] = [',', '_'];
+
+        pub fn as_char(&self) -> char {
+            match self {
+                Self::Comma => ',',
+                Self::Underscore => '_',
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct UnitFormatter {
+        decimals: u8,
+        precision: Option<usize>,
+        rounding: Rounding,
+        separator: Option<Separator>,
+        fixed: bool,
+    }
+
+    impl UnitFormatter {
+        pub fn new(decimals: u8) -> Self {
+            Self {
+                decimals,
+                precision: None,
+                rounding: Rounding::Down,
+                separator: None,
+                fixed: false,
+            }
+        }
+
+        pub fn unit(unit: Unit) -> Self {
+            Self::new(unit.decimals())
+        }
+
+        // at most `precision` fraction digits, the rest is rounded away
+        pub fn with_precision(mut self, precision: usize) -> Self {
+            self.precision = Some(precision);
+            self
+        }
+
+        pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+            self.rounding = rounding;
+            self
+        }
+
+        // groups the integer part by thousands
+        pub fn with_separator(mut self, separator: Separator) -> Self {
+            self.separator = Some(separator);
+            self
+        }
+
+        // keeps trailing zeros up to the precision, "1.50" instead of "1.5"
+        pub fn with_fixed(mut self, fixed: bool) -> Self {
+            self.fixed = fixed;
+            self
+        }
+
+        pub fn format(&self, amount: &U256) -> String {
+            let decimals = self.decimals as usize;
+            let mut digits = amount.to_string().into_bytes();
+            if digits.len() <= decimals {
+                let mut padded = vec![b'0'; decimals + 1 - digits.len()];
+                padded.extend_from_slice(&digits);
+                digits = padded;
+            }
+            let mut int_len = digits.len() - decimals;
+
+            if let Some(precision) = self.precision.filter(|n| *n < decimals) {
+                let keep = int_len + precision;
+                if self.rounding.round_up(digits[keep - 1], &digits[keep..]) {
+                    digits.truncate(keep);
+                    if increment(&mut digits) {
+                        int_len += 1;
+                    }
+                } else {
+                    digits.truncate(keep);
+                }
+            }
+
+            let (int, frac) = digits.split_at(int_len);
+            let mut frac = std::str::from_utf8(frac).unwrap().to_owned();
+            match (self.fixed, self.precision) {
+                (true, Some(precision)) => {
+                    frac.push_str(&"0".repeat(precision.saturating_sub(frac.len())))
+                }
+                (true, None) => {}
+                (false, _) => frac.truncate(frac.trim_end_matches('0').len()),
+            }
+
+            let mut out = group(std::str::from_utf8(int).unwrap(), self.separator);
+            if !frac.is_empty() {
+                out.push('.');
+                out.push_str(&frac);
+            }
+            out
+        }
+    }
+
+    // adds one to a decimal digit string, true when it grew by a digit
+    fn increment(digits: &mut Vec<u8>) -> bool {
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                return false;
+            }
+        }
+        digits.insert(0, b'1');
+        true
+    }
+
+    fn group(int: &str, separator: Option<Separator>) -> String {
+        let Some(separator) = separator else {
+            return int.to_owned();
+        };
+        // the leading group takes the digits left over by the groups of three
+        let head = match int.len() % 3 {
+            0 => int.len().min(3),
+            n => n,
+        };
+        let (head, rest) = int.split_at(head);
+        let mut out = String::with_capacity(int.len() + int.len() / 3);
+        out.push_str(head);
+        for chunk in rest.as_bytes().chunks(3) {
+            out.push(separator.as_char());
+            out.push_str(std::str::from_utf8(chunk).unwrap());
+        }
+        out
+    }
+
+    // exact, `parse_units(&format_units(n, d), d) == n`
+    pub fn format_units(amount: &U256, decimals: u8) -> String {
+        UnitFormatter::new(decimals).format(amount)
+    }
+
+    pub fn format_ether(wei: &U256) -> String {
+        format_units(wei, Unit::Ether.decimals())
+    }
+
+    pub fn format_gwei(wei: &U256) -> String {
+        format_units(wei, Unit::Gwei.decimals())
+    }
+
+    // "1,234.5" or "1_234.5", fraction digits beyond `decimals` are an error
+    // unless they are zeros
+    pub fn parse_units(s: &str, decimals: u8) -> Result<U256, UnitError> {
+        let invalid = || UnitError::Invalid(s.to_owned());
+        let value = s.trim();
+        let (int, frac) = match value.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (value, ""),
+        };
+        let groups: Vec<&str> = int.split(Separator::ALL).collect();
+        if groups.len() > 1
+            && (groups[0].is_empty()
+                || groups[0].len() > 3
+                || groups[1..].iter().any(|n| n.len() != 3))
+        {
+            return Err(invalid());
+        }
+        let int = groups.concat();
+        let all_digits = |n: &str| n.bytes().all(|n| n.is_ascii_digit());
+        if int.is_empty() && frac.is_empty() || !all_digits(&int) || !all_digits(frac) {
+            return Err(invalid());
+        }
+
+        let frac = frac.trim_end_matches('0');
+        if frac.len() > decimals as usize {
+            return Err(UnitError::Precision {
+                value: s.to_owned(),
+                decimals,
+            });
+        }
+        let mut digits = int;
+        digits.push_str(frac);
+        digits.push_str(&"0".repeat(decimals as usize - frac.len()));
+        let digits = digits.trim_start_matches('0');
+        if digits.is_empty() {
+            return Ok(U256::ZERO);
+        }
+        U256::from_str_radix(digits, 10).map_err(|_| UnitError::Overflow(s.to_owned()))
+    }
+
+    // "1.5 gwei", "2ether", "100 wei", a bare number is ether
+    pub fn parse_amount(s: &str) -> Result<U256, UnitError> {
+        let value = s.trim();
+        let split = value
+            .find(|n: char| n.is_ascii_alphabetic())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let unit = match unit {
+            "" => Unit::Ether,
+            unit => unit.parse()?,
+        };
+        parse_units(number.trim_end(), unit.decimals())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn ether(s: &str) -> U256 {
+            parse_units(s, 18).unwrap()
+        }
+
+        #[test]
+        fn test_round_trip() {
+            for (n, decimals) in [
+                (U256::ZERO, 18),
+                (U256::from(1), 18),
+                (U256::from(1_500_000_000_000_000_000_u128), 18),
+                (U256::from(123_456_789), 6),
+                (U256::from(42), 0),
+                (U256::MAX, 18),
+                (U256::MAX, 77),
+                (U256::MAX, 78),
+            ] {
+                let s = format_units(&n, decimals);
+                assert_eq!(parse_units(&s, decimals).unwrap(), n, "{}", s);
+            }
+            assert_eq!(format_ether(&U256::from(1)), "0.000000000000000001");
+            assert_eq!(format_gwei(&U256::from(1_500_000_000)), "1.5");
+        }
+
+        #[test]
+        fn test_rounding() {
+            let cases = [
+                ("1.25", Rounding::Down, "1.2"),
+                ("1.25", Rounding::Up, "1.3"),
+                ("1.25", Rounding::HalfUp, "1.3"),
+                ("1.25", Rounding::HalfEven, "1.2"),
+                ("1.35", Rounding::HalfEven, "1.4"),
+                ("1.2501", Rounding::HalfEven, "1.3"),
+                ("1.21", Rounding::Up, "1.3"),
+                ("1.2", Rounding::Up, "1.2"),
+            ];
+            for (value, rounding, expected) in cases {
+                let formatter = UnitFormatter::new(18)
+                    .with_precision(1)
+                    .with_rounding(rounding);
+                assert_eq!(
+                    formatter.format(&ether(value)),
+                    expected,
+                    "{} {:?}",
+                    value,
+                    rounding
+                );
+            }
+
+            // the carry runs into the integer part
+            let formatter = UnitFormatter::new(18)
+                .with_precision(1)
+                .with_rounding(Rounding::HalfUp);
+            assert_eq!(formatter.format(&ether("9.99")), "10");
+            assert_eq!(formatter.format(&ether("999.96")), "1000");
+            let formatter = formatter.with_fixed(true).with_separator(Separator::Comma);
+            assert_eq!(formatter.format(&ether("999.96")), "1,000.0");
+            assert_eq!(
+                UnitFormatter::new(18)
+                    .with_precision(0)
+                    .with_rounding(Rounding::HalfEven)
+                    .format(&ether("0.5")),
+                "0"
+            );
+        }
+
+        #[test]
+        fn test_separator() {
+            let n = ether("1234567.5");
+            for separator in [Separator::Comma, Separator::Underscore] {
+                let s = UnitFormatter::new(18).with_separator(separator).format(&n);
+                assert_eq!(s, format!("1{0}234{0}567.5", separator.as_char()));
+                assert_eq!(parse_units(&s, 18).unwrap(), n);
+            }
+            assert_eq!(
+                UnitFormatter::new(18)
+                    .with_separator(Separator::Comma)
+                    .format(&ether("123")),
+                "123"
+            );
+            for (value, expected) in [("0", "0"), ("1234", "1,234"), ("123456", "123,456")] {
+                let s = UnitFormatter::new(18)
+                    .with_separator(Separator::Comma)
+                    .format(&ether(value));
+                assert_eq!(s, expected);
+            }
+            for invalid in ["1,23", ",123", "1234,567", "1,234,", "1.234,5", "1 234"] {
+                assert!(parse_units(invalid, 18).is_err(), "{}", invalid);
+            }
+            assert!(matches!(
+                parse_units("1.0000001", 6),
+                Err(UnitError::Precision { .. })
+            ));
+            assert_eq!(
+                parse_units("1.000000100", 7).unwrap(),
+                U256::from(10_000_001)
+            );
+            assert_eq!(parse_amount("1.5 gwei").unwrap(), U256::from(1_500_000_000));
+        }
+    }
+}
@@ -161,6 +161,18 @@ impl Time {
     pub fn checked_sub_time(&self, other: &Time) -> Option<Duration> {
         self.0.checked_sub(other.0)
     }
+
+    pub fn checked_add(&self, du: Duration) -> Option<Time> {
+        self.0.checked_add(du).map(Self)
+    }
+
+    pub fn saturating_add(&self, du: Duration) -> Time {
+        Self(self.0.saturating_add(du))
+    }
+
+    pub fn checked_sub(&self, du: Duration) -> Option<Time> {
+        self.0.checked_sub(du).map(Self)
+    }
 }
 
 impl Sub<Duration> for Time {
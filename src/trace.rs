@@ -158,6 +158,16 @@ impl Alive {
         }
     }
 
+    pub async fn wait_shutdown(&self) {
+        let max_sleep = Duration::from_secs(1);
+        while self.is_alive() {
+            tokio::select! {
+                _ = sleep(max_sleep) => {},
+                _ = self.alive.wait(false) => {},
+            }
+        }
+    }
+
     // pub fn recv<T>(&self, r: &mpsc::Receiver<T>) -> Result<T, mpsc::RecvTimeoutError> {
     //     let max_sleep = Duration::from_secs(1);
     //     loop {